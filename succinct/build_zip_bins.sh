@@ -2,7 +2,7 @@ RELEASE_DIR="target/release"
 
 cargo build --release
 mkdir -p bin
-for b in wt wt_n_bench bv_bench rank_bench sel_bench wt_access_bench wt_select_bench wt_sigma_bench bf bf_bench
+for b in wt wt_n_bench bv_bench rank_bench sel_bench wt_access_bench wt_select_bench wt_sigma_bench bf bf_bench trie wt_build_bench seq dna_bench
 do
     cp $RELEASE_DIR/$b bin/.
 done
@@ -0,0 +1,119 @@
+
+use succinct::bloom_filter::{BloomFilter, Query, MQ};
+use succinct::scalable_bloom_filter::ScalableBloomFilter;
+use succinct::xor_filter::XorFilter;
+use succinct::hashing::RawBytes;
+use serde::{Serialize, Deserialize};
+use std::env;
+
+use std::fs::{self, File};
+use std::io::{prelude::*, BufReader, BufWriter};
+use std::process;
+
+// Files start with MAGIC followed by the bincode encoded Filter. Older
+// versions of bf wrote a bare BloomFilter with string hashing; those files
+// are rejected and have to be rebuilt from the keys.
+const MAGIC: &[u8; 8] = b"SUCCBF\x00\x01";
+
+#[derive(Serialize, Deserialize)]
+enum Filter {
+    Bloom(BloomFilter),
+    Xor(XorFilter),
+    Scalable(ScalableBloomFilter),
+}
+
+fn main() {
+    /* Bloom filter app */
+    let args: Vec<String> = env::args().collect();
+    let subparser = &args[1];
+    if subparser == "build" {
+        // Usage 
+        //   bf build <key_file> <fpr> <n distinct keys> <output> [bloom|xor|scalable]
+        assert!(args.len() == 6 || args.len() == 7);
+        let key_file = &args[2];
+        let fpr: f32 = args[3].parse().unwrap();
+        let n_keys: usize = args[4].parse().unwrap();
+        let output = &args[5];
+        let kind = args.get(6).map_or("bloom", |s| s.as_str());
+
+        build(key_file, fpr, n_keys, output, kind);
+
+    } else if subparser == "query" {
+        // Usage 
+        //   bf query <bloom_filter> <queries>
+        assert_eq!(args.len(), 4);
+        let bf = &args[2];
+        let queries = &args[3];
+        query(bf, queries);
+    } else {
+        println!("{} - not implemented", subparser);
+    }
+}
+
+fn build(key_file: &str, fpr: f32, n_keys: usize, out_file: &str, kind: &str) {
+    let file = File::open(key_file).unwrap();
+    let reader = BufReader::new(file);
+    
+    let filter = match kind {
+        "bloom" => {
+            let mut bf = BloomFilter::with_fpr(fpr, n_keys);
+            for line in reader.lines() {
+                let line = line.unwrap();
+                bf.insert_bytes(line.as_bytes());
+            }
+            Filter::Bloom(bf)
+        }
+        "xor" => {
+            // Static filter: built from all keys at once
+            let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+            let keys: Vec<RawBytes> = lines.iter().map(|l| RawBytes(l.as_bytes())).collect();
+            match XorFilter::with_fpr(&keys, fpr) {
+                Ok(xf) => Filter::Xor(xf),
+                Err(e) => fail(&format!("cannot build xor filter: {}", e)),
+            }
+        }
+        "scalable" => {
+            // n distinct keys only sizes the first stage
+            let mut sbf = ScalableBloomFilter::new(fpr, n_keys);
+            for line in reader.lines() {
+                let line = line.unwrap();
+                sbf.insert_bytes(line.as_bytes());
+            }
+            eprintln!("{} stages, estimated fpr {}", sbf.n_stages(), sbf.estimated_fpr());
+            Filter::Scalable(sbf)
+        }
+        _ => panic!("unknown filter type {}, expected bloom, xor or scalable", kind),
+    };
+
+    let mut out = BufWriter::new(File::create(out_file).expect("Failed to create output"));
+    out.write_all(MAGIC).expect("Failed to write output");
+    bincode::serialize_into(out, &filter).expect("Failed to write output");
+}
+
+fn query(bf_fp: &str, query_file: &str) {
+    let data = fs::read(bf_fp).expect("Error");
+    if !data.starts_with(MAGIC) {
+        fail(&format!("{} was not written by this version of bf build; rebuild it", bf_fp));
+    }
+    let filter: Filter = bincode::deserialize(&data[MAGIC.len()..])
+        .unwrap_or_else(|e| fail(&format!("cannot read {}: {}", bf_fp, e)));
+
+    let file = File::open(query_file).unwrap();
+    let reader = BufReader::new(file);
+    
+    for line in reader.lines() {
+        let line = line.unwrap();
+        let isin = match &filter {
+            Filter::Bloom(bf) => bf.query_bytes(line.as_bytes()),
+            Filter::Xor(xf) => xf.query_bytes(line.as_bytes()),
+            Filter::Scalable(sbf) => sbf.query_bytes(line.as_bytes()),
+        };
+        let ans = if isin { 'Y' } else { 'N' };
+        println!("{}:{}", line, ans);
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("bf: {}", msg);
+    process::exit(1)
+}
\ No newline at end of file
@@ -0,0 +1,134 @@
+use succinct::bloom_filter::{BlockedBloomFilter, BloomFilter, PartitionedBloomFilter, RegisterBlockedBloomFilter, MQ};
+use succinct::hashing::HashScheme;
+use std::hash::Hash;
+
+use std::time::Instant;
+use std::cmp::min;
+
+
+
+fn main() {
+    /*
+    USAGE:
+        ./bf_bench
+    */
+    
+    // Sample Ns from log space
+    let n_ns = 20;
+    // let max_n = 5000000;
+    let max_n = 10000000;
+    let min_n = 1000;
+
+    let log_max_n = (max_n as f32).ln();
+    let log_min_n = (min_n as f32).ln();
+
+    let log_incr_n = (log_max_n - log_min_n) / (n_ns - 1) as f32;
+    let mut ns: Vec<usize> = (0..n_ns).map(|x| (x as f32 * log_incr_n + log_min_n).exp() as usize).collect();
+    ns[n_ns - 1] = max_n;
+
+    let n_fprs = 10;
+    let min_fpr = 0.01;
+    let max_fpr = 0.25;
+
+    let incr_fpr = (max_fpr - min_fpr) / (n_fprs - 1) as f32;
+    let fprs: Vec<f32> = (0..n_fprs).map(|x| x as f32 * incr_fpr + min_fpr).collect();
+
+    println!( "{}\t{}\t{}\t{}\t{}\t{}\t{}", "N", "FPR", "empirical_FPR",  "query_time", "frac_query_inserted", "amq", "amq_size");
+
+    // Total query time of each filter with seeded and double hashing probes
+    let mut seeded_ns = [0.0_f32; 3];
+    let mut double_ns = [0.0_f32; 3];
+
+    for n in &ns {
+        for fpr in &fprs {
+            let insert_up_to = *n;
+            let dh = HashScheme::DoubleHashing;
+            let mut bf = BloomFilter::with_fpr(*fpr, *n);
+            let mut bbf = BlockedBloomFilter::with_fpr(*fpr, *n, 64_usize);
+            let mut rbbf = RegisterBlockedBloomFilter::with_fpr(*fpr, *n, 512);
+            let mut rbbf64 = RegisterBlockedBloomFilter::with_fpr(*fpr, *n, 64);
+            let mut pbf = PartitionedBloomFilter::with_fpr(*fpr, *n);
+            let mut bf_dh = BloomFilter::with_fpr(*fpr, *n).with_scheme(dh);
+            let mut bbf_dh = BlockedBloomFilter::with_fpr(*fpr, *n, 64_usize).with_scheme(dh);
+            let mut pbf_dh = PartitionedBloomFilter::with_fpr(*fpr, *n).with_scheme(dh);
+            insert_amq_up_to(insert_up_to, &mut bf);
+            insert_amq_up_to(insert_up_to, &mut bbf);
+            insert_amq_up_to(insert_up_to, &mut rbbf);
+            insert_amq_up_to(insert_up_to, &mut rbbf64);
+            insert_amq_up_to(insert_up_to, &mut pbf);
+            insert_amq_up_to(insert_up_to, &mut bf_dh);
+            insert_amq_up_to(insert_up_to, &mut bbf_dh);
+            insert_amq_up_to(insert_up_to, &mut pbf_dh);
+
+            let window_size = min(min_n, 5000);
+            // (fraction of queries inserted, query range)
+            let ranges = [
+                ("0.", insert_up_to, insert_up_to + window_size),
+                ("0.5", insert_up_to - (window_size / 2), insert_up_to + (window_size / 2)),
+                ("1.", 0, window_size),
+            ];
+
+            for &(frac, from, to) in ranges.iter() {
+                let row = (*n, *fpr, frac, insert_up_to, from, to);
+                seeded_ns[0] += report(row, "bf", &bf, bf.len());
+                seeded_ns[1] += report(row, "bbf", &bbf, bbf.len());
+                report(row, "rbbf512", &rbbf, rbbf.len());
+                report(row, "rbbf64", &rbbf64, rbbf64.len());
+                seeded_ns[2] += report(row, "pbf", &pbf, pbf.len());
+                double_ns[0] += report(row, "bf_dh", &bf_dh, bf_dh.len());
+                double_ns[1] += report(row, "bbf_dh", &bbf_dh, bbf_dh.len());
+                double_ns[2] += report(row, "pbf_dh", &pbf_dh, pbf_dh.len());
+            }
+        }
+    }
+
+    // Speedup of double hashing over seeded probes, on stderr to keep the table clean
+    for (i, name) in ["bf", "bbf", "pbf"].iter().enumerate() {
+        eprintln!("{} double hashing speedup: {:.2}x", name, seeded_ns[i] / double_ns[i]);
+    }
+}
+
+fn report<T: MQ>(row: (usize, f32, &str, usize, usize, usize), name: &str, amq: &T, amq_size: usize) -> f32 {
+    // Print one table row, return the mean query time
+    let (n, fpr, frac, insert_up_to, query_from, query_to) = row;
+    let (e_fpr, ns_per_query) = benchmark_amq(insert_up_to, query_from, query_to, amq);
+    println!( "{}\t{}\t{}\t{}\t{}\t{}\t{}", n, fpr, e_fpr, ns_per_query, frac, name, amq_size);
+    ns_per_query
+}
+
+fn timed_query<H: Hash, T: MQ>(x: &H, amq: &T) -> (bool, usize) {
+    let t = Instant::now();
+    let isin = amq.query(x);
+    let elapsed = t.elapsed().as_nanos() as usize;
+    (isin, elapsed)
+}
+
+fn insert_amq_up_to<T: MQ>(insert_up_to: usize, amq: &mut T) {
+    for i in 0..(insert_up_to) {
+        amq.insert(&i);
+    }
+}
+fn benchmark_amq<T: MQ>(insert_up_to: usize, query_from: usize, query_to: usize, amq: &T) -> (f32, f32) {
+    let mut n_true_neg = 0;
+    let mut n_false_pos = 0;
+    let mut total_elapsed = 0;
+    let mut n_queries = 0;
+
+    for i in query_from..query_to {
+        n_queries += 1;
+        let (isin, elapsed) = timed_query(&i, amq);
+
+        if i >= insert_up_to {
+            if isin {
+                n_false_pos += 1;
+            }
+            n_true_neg += 1;
+        }
+        total_elapsed += elapsed;
+    }
+
+    let fpr = n_false_pos as f32 / n_true_neg as f32;
+    let ns_per_query = total_elapsed as f32 / n_queries as f32;
+    
+    (fpr, ns_per_query)
+}
\ No newline at end of file
@@ -0,0 +1,77 @@
+use succinct::wt::WT;
+use succinct::dna::DnaSeq;
+
+use std::env;
+use std::time::Instant;
+use rand::{Rng, thread_rng};
+
+fn main() {
+    /*
+    USAGE:
+        ./dna_bench [REPEATS] [STRING_LEN]
+
+    Times ACCESS, RANK and SELECT for the wavelet tree and the 2-bit DnaSeq
+    over the same random DNA string (ACGT with occasional runs of N), i.e. the
+    sigma = 5 point of wt_sigma_bench.
+
+    Outputs to stdout:
+        2 lines corresponding to args of the run
+        6 lines with format <struct>\t<op>\t<time>\t<overhead> where:
+            <struct> is WT or DnaSeq
+            <op> is access, rank or select
+            <time> is the average time of the operation in nanoseconds
+            <overhead> is the size of the datastructure in bytes.
+    */
+
+    let args: Vec<String> = env::args().collect();
+    assert_eq!(args.len(), 3);
+    let repeats: usize = args[1].parse().unwrap();
+    let string_len: usize = args[2].parse().unwrap();
+
+    println!("repeats {}", repeats);
+    println!("string length {}", string_len);
+
+    let mut rng = thread_rng();
+    let bases = ['A', 'C', 'G', 'T'];
+    let mut s = String::with_capacity(string_len);
+    while s.len() < string_len {
+        if rng.gen_range(0, 1000) == 0 {
+            let run = rng.gen_range(1, 100);
+            s.extend(std::iter::repeat_n('N', run));
+        } else {
+            s.push(bases[rng.gen_range(0, 4)]);
+        }
+    }
+    s.truncate(string_len);
+
+    let wt = WT::new(&s);
+    let dna = DnaSeq::new(&s);
+    let positions: Vec<usize> = (0..repeats).map(|_| rng.gen_range(0, string_len)).collect();
+    let max_rank = dna.count('G');
+    let ranks: Vec<usize> = (0..repeats).map(|_| rng.gen_range(1, max_rank + 1)).collect();
+
+    let t = Instant::now();
+    for &i in positions.iter() { wt.access(i); }
+    report("WT", "access", t, repeats, wt.size_of());
+    let t = Instant::now();
+    for &i in positions.iter() { wt.rank('G', i); }
+    report("WT", "rank", t, repeats, wt.size_of());
+    let t = Instant::now();
+    for &r in ranks.iter() { wt.select('G', r); }
+    report("WT", "select", t, repeats, wt.size_of());
+
+    let t = Instant::now();
+    for &i in positions.iter() { dna.access(i); }
+    report("DnaSeq", "access", t, repeats, dna.size_of());
+    let t = Instant::now();
+    for &i in positions.iter() { dna.rank('G', i); }
+    report("DnaSeq", "rank", t, repeats, dna.size_of());
+    let t = Instant::now();
+    for &r in ranks.iter() { dna.select('G', r); }
+    report("DnaSeq", "select", t, repeats, dna.size_of());
+}
+
+fn report(name: &str, op: &str, t: Instant, repeats: usize, size: usize) {
+    let elapsed = (t.elapsed().as_nanos() as f32) / (repeats as f32);
+    println!("{}\t{}\t{}\t{}", name, op, elapsed, size);
+}
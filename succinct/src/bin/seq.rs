@@ -0,0 +1,94 @@
+use succinct::seq::SeqIndex;
+use std::env;
+
+use std::fs::{self, File};
+use std::io::BufReader;
+
+
+fn main() {
+    /* Sequence index app: wavelet tree over FASTA/FASTQ records, queried by
+       record name and offset within the record. */
+    let args: Vec<String> = env::args().collect();
+    let subparser = &args[1];
+    assert_eq!(args.len(), 4);
+    if subparser == "build" {
+        // Usage
+        //   seq build <fasta or fastq> <output>
+        build(&args[2], &args[3]);
+    } else if subparser == "access" {
+        // Usage
+        //   seq access <index> <queries>, lines of <record>\t<offset>
+        access(&args[2], &args[3]);
+    } else if subparser == "rank" {
+        // Usage
+        //   seq rank <index> <queries>, lines of <c>\t<record>\t<offset>
+        rank(&args[2], &args[3]);
+    } else if subparser == "select" {
+        // Usage
+        //   seq select <index> <queries>, lines of <c>\t<record>\t<rank>
+        select(&args[2], &args[3]);
+    } else {
+        println!("{} - not implemented", subparser);
+    }
+}
+
+fn build(in_file: &str, out_file: &str) {
+    let file = File::open(in_file).expect("Failed to open input");
+    let idx = SeqIndex::from_reader(BufReader::new(file)).expect("Failed to parse input");
+
+    println!("{}", idx.records().n_records());
+    println!("{}", idx.records().len());
+
+    let encoded = bincode::serialize(&idx).unwrap();
+    fs::write(out_file, encoded).expect("Failed to write output");
+}
+
+fn access(idx_fp: &str, query_file: &str) {
+    let idx = load_index(idx_fp);
+    for r in read_queries(query_file) {
+        let record = record_id(&idx, &r[0]);
+        let offset = r[1].parse::<usize>().expect("Cannot parse offset");
+        println!("{}", idx.access(record, offset));
+    }
+}
+
+fn rank(idx_fp: &str, query_file: &str) {
+    let idx = load_index(idx_fp);
+    for r in read_queries(query_file) {
+        let c = r[0].chars().next().expect("Cannot parse character");
+        let record = record_id(&idx, &r[1]);
+        let offset = r[2].parse::<usize>().expect("Cannot parse offset");
+        println!("{}", idx.rank(c, record, offset));
+    }
+}
+
+fn select(idx_fp: &str, query_file: &str) {
+    let idx = load_index(idx_fp);
+    for r in read_queries(query_file) {
+        let c = r[0].chars().next().expect("Cannot parse character");
+        let record = record_id(&idx, &r[1]);
+        let i = r[2].parse::<usize>().expect("Cannot parse rank");
+        match idx.select(c, record, i) {
+            Some(offset) => println!("{}", offset),
+            None => println!("Not found"),
+        }
+    }
+}
+
+fn record_id(idx: &SeqIndex, name: &str) -> usize {
+    idx.records().id_of(name).expect("Unknown record")
+}
+
+fn read_queries(fp: &str) -> Vec<csv::StringRecord> {
+    let file = File::open(fp).expect("Error");
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .from_reader(BufReader::new(file));
+    rdr.records().map(|r| r.expect("Error")).collect()
+}
+
+fn load_index(fp: &str) -> SeqIndex {
+    let data = fs::read(fp).expect("Error");
+    bincode::deserialize(&data[..]).unwrap()
+}
@@ -0,0 +1,72 @@
+use succinct::trie::Trie;
+use std::env;
+
+use std::fs::{self, File};
+use std::io::{prelude::*, BufReader};
+
+
+fn main() {
+    /* Succinct trie app */
+    let args: Vec<String> = env::args().collect();
+    let subparser = &args[1];
+    if subparser == "build" {
+        // Usage
+        //   trie build <key_file> <output>
+        assert_eq!(args.len(), 4);
+        build(&args[2], &args[3]);
+    } else if subparser == "query" {
+        // Usage
+        //   trie query <trie> <queries>
+        assert_eq!(args.len(), 4);
+        query(&args[2], &args[3]);
+    } else if subparser == "prefix" {
+        // Usage
+        //   trie prefix <trie> <prefix>
+        assert_eq!(args.len(), 4);
+        prefix(&args[2], &args[3]);
+    } else {
+        println!("{} - not implemented", subparser);
+    }
+}
+
+fn build(key_file: &str, out_file: &str) {
+    let file = File::open(key_file).unwrap();
+    let reader = BufReader::new(file);
+
+    let mut keys: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+    keys.sort();
+    let trie = Trie::new(&keys);
+
+    println!("{}", trie.len());
+    println!("{}", trie.n_nodes());
+
+    let encoded = bincode::serialize(&trie).unwrap();
+    fs::write(out_file, encoded).expect("Failed to write output");
+}
+
+fn query(trie_fp: &str, query_file: &str) {
+    let trie = load_trie(trie_fp);
+
+    let file = File::open(query_file).unwrap();
+    let reader = BufReader::new(file);
+
+    for line in reader.lines() {
+        let line = line.unwrap();
+        match trie.id_of(&line) {
+            Some(id) => println!("{}:Y\t{}", line, id),
+            None => println!("{}:N", line),
+        }
+    }
+}
+
+fn prefix(trie_fp: &str, prefix: &str) {
+    let trie = load_trie(trie_fp);
+    for key in trie.prefix_iter(prefix) {
+        println!("{}", key);
+    }
+}
+
+fn load_trie(fp: &str) -> Trie {
+    let data = fs::read(fp).expect("Error");
+    bincode::deserialize(&data[..]).unwrap()
+}
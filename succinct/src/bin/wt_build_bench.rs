@@ -0,0 +1,68 @@
+use succinct::wt::WT;
+
+use std::env;
+use std::time::Instant;
+use std::iter;
+use rand::{Rng, thread_rng};
+use rand::distributions::Alphanumeric;
+
+fn main() {
+    /*
+    USAGE:
+        ./wt_build_bench [REPEATS] [MAX_EXP] [THREADS]
+
+    Times wavelet tree construction for random alphanumeric strings of length
+    2^10, 2^11, ..., 2^MAX_EXP with the sequential builder and the parallel
+    builder using 1, 2, 4, ... up to THREADS threads.
+
+    Outputs to stdout:
+        3 lines corresponding to args of the run
+        one line per size with format <size>\t<seq>\t<par_1>\t<par_2>... where:
+            <size> is the length of the string
+            <seq> is the average sequential construction time in milliseconds
+            <par_t> is the average parallel construction time with t threads in milliseconds
+    */
+
+    let args: Vec<String> = env::args().collect();
+    assert_eq!(args.len(), 4);
+    let repeats: usize = args[1].parse().unwrap();
+    let power: usize = args[2].parse().unwrap();
+    let max_threads: usize = args[3].parse().unwrap();
+
+    println!("Repeats: {}", repeats);
+    println!("Max_exp Chars: {}", power);
+    println!("Max Threads: {}", max_threads);
+
+    let mut threads = vec![];
+    let mut t = 1;
+    while t <= max_threads {
+        threads.push(t);
+        t *= 2;
+    }
+
+    let mut rng = thread_rng();
+    for p in 10..power + 1 {
+        let s = 2_usize.pow(p as u32);
+        let chars: String = iter::repeat(())
+                            .map(|()| rng.sample(Alphanumeric))
+                            .take(s)
+                            .collect();
+
+        let t = Instant::now();
+        for _ in 0..repeats {
+            WT::new(&chars);
+        }
+        let seq = t.elapsed().as_secs_f64() * 1000.0 / repeats as f64;
+
+        let mut row = format!("{}\t{}", s, seq);
+        for &n_threads in threads.iter() {
+            let t = Instant::now();
+            for _ in 0..repeats {
+                WT::new_parallel(&chars, n_threads);
+            }
+            let par = t.elapsed().as_secs_f64() * 1000.0 / repeats as f64;
+            row.push_str(&format!("\t{}", par));
+        }
+        println!("{}", row);
+    }
+}
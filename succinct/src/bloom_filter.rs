@@ -1,81 +1,211 @@
-use super::bv::BitVec;
+use super::bv::{BitVec, IntVec};
 use super::math::cdiv;
 
-use seahash::hash_seeded;
 use rand::Rng;
-use std::hash::{Hash, Hasher};
+use std::hash::Hash;
 use std::f32::consts::LN_2;
+use std::fmt;
+use super::space::{SpaceBreakdown, SpaceUsage};
+use super::hashing::{n_seeds, probes, reduce, seeded_hash, seeds_from, FilterHasher, HashScheme, Probes, RawBytes, SeaHash};
 use serde::{Serialize, Deserialize};
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BlockedBloomFilter{
+pub struct BlockedBloomFilter<F = SeaHash> {
     bv: BitVec,
     k: usize,
     seeds: Vec<u64>,
+    seed: u64,
     nb: usize,
     b_size: usize,
+    scheme: HashScheme,
+    hasher: F,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BloomFilter {
+pub struct BloomFilter<F = SeaHash> {
     bv: BitVec,
     k: usize,
     n: usize,
     seeds: Vec<u64>,
+    seed: u64,
+    scheme: HashScheme,
+    hasher: F,
 }
 
-pub trait MQ {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountingBloomFilter<F = SeaHash> {
+    // Bloom filter with a w bit counter per slot instead of a bit, so items
+    // can be removed. Counters saturate at 2^w - 1 and then stay there,
+    // since the true count is no longer known.
+    counters: IntVec,
+    k: usize,
+    n: usize,
+    seeds: Vec<u64>,
+    seed: u64,
+    scheme: HashScheme,
+    hasher: F,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterBlockedBloomFilter<F = SeaHash> {
+    // Bloom filter whose blocks are one 64 bit word or one 512 bit cache
+    // line of `words`. A single hash picks the block and all k bit positions
+    // in it (by double hashing within the block), and inserts and queries
+    // work on whole words through per-word masks.
+    words: Vec<u64>,
+    k: usize,
+    nb: usize,
+    block_words: usize,
+    seeds: Vec<u64>,
+    seed: u64,
+    hasher: F,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionedBloomFilter<F = SeaHash> {
+    // Bloom filter split into k disjoint partitions of `part` bits; hash i
+    // only sets bits in partition i, so the k probes never collide.
+    bv: BitVec,
+    k: usize,
+    part: usize,
+    seeds: Vec<u64>,
+    seed: u64,
+    scheme: HashScheme,
+    hasher: F,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterError {
+    // The filter has no room left for the item
+    Full,
+    // Filters differ in size, number of hashes, seeds or hash scheme
+    Incompatible,
+    // A static filter could not be built over the keys
+    BuildFailed,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::Full => write!(f, "filter is full"),
+            FilterError::Incompatible => write!(f, "filters differ in size, hashes or seeds"),
+            FilterError::BuildFailed => write!(f, "filter construction failed"),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+pub trait Query {
+    /* Approximate membership query: no false negatives */
+    fn query<H: Hash>(&self, item: &H) -> bool;
+
+    fn query_bytes(&self, bytes: &[u8]) -> bool {
+        // Portable query, see RawBytes
+        self.query(&RawBytes(bytes))
+    }
+}
+
+pub trait MQ: Query {
     /* Membership Query */
     fn insert<H: Hash>(&mut self, item: &H);
-    fn query<H: Hash>(&self, item: &H) -> bool;
+
+    fn insert_bytes(&mut self, bytes: &[u8]) {
+        // Portable insert, see RawBytes
+        self.insert(&RawBytes(bytes))
+    }
 }
 
-impl MQ for BlockedBloomFilter {
+impl<F: FilterHasher> MQ for BlockedBloomFilter<F> {
     fn insert<H: Hash>(&mut self, item: &H) {
-        let n = self.len();
-        let hx_block = self.hash_block(item);
-        for i in 0..self.n_hashes() {
-            let hx_in_block = self.hash_in_block(i, item);
-            self.bv.set(hx_block + hx_in_block, true);
+        let (b_size, nb) = (self.block_size(), self.n_blocks());
+        let mut hashes = probes(&self.hasher, self.scheme, &self.seeds, self.k, item);
+
+        // First hash is used for block
+        let hx_block = reduce(hashes.next().unwrap(), nb) * b_size;
+
+        // next n-1 hashes are used 
+        for h in hashes {
+            self.bv.set(hx_block + reduce(h, b_size), true);
         }
     }
+}
 
+impl<F: FilterHasher> Query for BlockedBloomFilter<F> {
     fn query<H: Hash>(&self, item: &H) -> bool {
-        let mut isin = true;
+        let mut hashes = self.probes(item);
 
         // First hash is used for block
-        let hx_block = self.hash_block(item);
+        let hx_block = reduce(hashes.next().unwrap(), self.n_blocks()) * self.block_size();
 
         // next n-1 hashes are used 
-        for i in 1..self.n_hashes() {
-            let hx_in_block = self.hash_in_block(i, item);
-            isin &= self.bv.get(hx_block + hx_in_block);
-            if !isin {
-                return isin;
-            }
-        }
-        isin
+        hashes.all(|h| self.bv.get(hx_block + reduce(h, self.block_size())))
     }
 }
 
-impl MQ for BloomFilter {
+impl<F: FilterHasher> MQ for BloomFilter<F> {
     fn insert<H: Hash>(&mut self, item: &H) {
         let n = self.len();
-        for i in 0..self.n_hashes() {
-            self.bv.set(self.hash_i(i, item), true);
+        for h in probes(&self.hasher, self.scheme, &self.seeds, self.k, item) {
+            self.bv.set(reduce(h, n), true);
+        }
+    }
+}
+
+impl<F: FilterHasher> Query for BloomFilter<F> {
+    fn query<H: Hash>(&self, item: &H) -> bool {
+        self.probes(item).all(|h| self.bv.get(reduce(h, self.len())))
+    }
+}
+
+impl<F: FilterHasher> MQ for RegisterBlockedBloomFilter<F> {
+    fn insert<H: Hash>(&mut self, item: &H) {
+        let (block, masks) = self.masks(item);
+        for (j, m) in masks[..self.block_words].iter().enumerate() {
+            self.words[block + j] |= m;
         }
     }
+}
+
+impl<F: FilterHasher> Query for RegisterBlockedBloomFilter<F> {
+    fn query<H: Hash>(&self, item: &H) -> bool {
+        let (block, masks) = self.masks(item);
+        masks[..self.block_words].iter().enumerate().all(|(j, &m)| self.words[block + j] & m == m)
+    }
+}
+
+impl<F: FilterHasher> MQ for PartitionedBloomFilter<F> {
+    fn insert<H: Hash>(&mut self, item: &H) {
+        let part = self.part;
+        for (i, h) in probes(&self.hasher, self.scheme, &self.seeds, self.k, item).enumerate() {
+            self.bv.set(i * part + reduce(h, part), true);
+        }
+    }
+}
 
+impl<F: FilterHasher> Query for PartitionedBloomFilter<F> {
     fn query<H: Hash>(&self, item: &H) -> bool {
-        let mut isin = true;
-        for i in 0..self.n_hashes() {
-            isin &= self.bv.get(self.hash_i(i, item));
-            if !isin {
-                return isin;
+        self.probes(item).enumerate().all(|(i, h)| self.bv.get(i * self.part + reduce(h, self.part)))
+    }
+}
+
+impl<F: FilterHasher> MQ for CountingBloomFilter<F> {
+    fn insert<H: Hash>(&mut self, item: &H) {
+        let (max, n) = (self.max_count(), self.len());
+        for h in probes(&self.hasher, self.scheme, &self.seeds, self.k, item) {
+            let h = reduce(h, n);
+            let c = self.counters.get_int(h);
+            if c < max {
+                self.counters.set_int(h, c + 1);
             }
         }
-        isin
+    }
+}
+
+impl<F: FilterHasher> Query for CountingBloomFilter<F> {
+    fn query<H: Hash>(&self, item: &H) -> bool {
+        self.probes(item).all(|h| self.counters.get_int(reduce(h, self.len())) > 0)
     }
 }
 
@@ -83,17 +213,22 @@ impl MQ for BloomFilter {
 impl BlockedBloomFilter {
     pub fn new(k: usize, n_blocks: usize, block_size: usize) -> Self{
         // block_size in bytes
+        Self::with_seed(k, n_blocks, block_size, rand::thread_rng().gen::<u64>())
+    }
 
-        let mut rng = rand::thread_rng();
-        let n_seeds = k * 4; //TODO: or is it k+1?
-        let seeds = (0..n_seeds).map(|_x| rng.gen::<u64>()).collect();
+    pub fn with_seed(k: usize, n_blocks: usize, block_size: usize, seed: u64) -> Self {
+        // Same seed and parameters give the same filter on every platform
+        let seeds = seeds_from(seed, n_seeds(k));
 
         Self {
             bv: BitVec::new(n_blocks * block_size * 8),
             k: k,
             nb: n_blocks,
             seeds: seeds,
+            seed,
             b_size: block_size * 8,
+            scheme: HashScheme::default(),
+            hasher: SeaHash,
         }
     }
 
@@ -105,6 +240,22 @@ impl BlockedBloomFilter {
 
         Self::new(k, n_blocks, block_size)
     }
+}
+
+impl<F: FilterHasher> BlockedBloomFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> BlockedBloomFilter<G> {
+        // Switch the hash function; only valid before inserting
+        BlockedBloomFilter {
+            bv: self.bv,
+            k: self.k,
+            seeds: self.seeds,
+            seed: self.seed,
+            nb: self.nb,
+            b_size: self.b_size,
+            scheme: self.scheme,
+            hasher,
+        }
+    }
 
     pub fn n_hashes(&self) -> usize {
         self.k
@@ -122,37 +273,47 @@ impl BlockedBloomFilter {
         self.nb
     }
 
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+
+    pub fn with_scheme(mut self, scheme: HashScheme) -> Self {
+        // Switch how probe hashes are derived; only valid before inserting
+        self.scheme = scheme;
+        self
+    }
 
-    fn hash_block<H: Hash>(&self, item: &H) -> usize {
-        self.hash_i_mod(0, item, self.n_blocks()) * self.block_size()
+    pub fn scheme(&self) -> HashScheme {
+        self.scheme
     }
 
-    fn hash_in_block<H: Hash>(&self, i: usize, item: &H) -> usize {
-        self.hash_i_mod(i, item, self.block_size())
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
-    fn hash_i_mod<H: Hash>(&self, i: usize, item: &H, m: usize) -> usize {
-        let s_i = i * 4;
-        let mut hasher = seahash::SeaHasher::with_seeds(self.seeds[s_i],
-                                                        self.seeds[s_i + 1],
-                                                        self.seeds[s_i + 2],
-                                                        self.seeds[s_i + 3]);
-        item.hash(&mut hasher);
-        hasher.finish() as usize % m
+    fn probes<'a, H: Hash>(&'a self, item: &'a H) -> Probes<'a, F, H> {
+        probes(&self.hasher, self.scheme, &self.seeds, self.k, item)
     }
 }
 
 impl BloomFilter {
     pub fn new(k: usize, n: usize) -> Self{
-        let mut rng = rand::thread_rng();
-        let n_seeds = k * 4;
-        let seeds = (0..n_seeds).map(|_x| rng.gen::<u64>()).collect();
+        Self::with_seed(k, n, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed(k: usize, n: usize, seed: u64) -> Self {
+        // Same seed and parameters give the same filter on every platform
+        let seeds = seeds_from(seed, n_seeds(k));
 
         Self {
             bv: BitVec::new(n),
             k: k,
             n: n,
-            seeds: seeds
+            seeds: seeds,
+            seed,
+            scheme: HashScheme::default(),
+            hasher: SeaHash,
         }
     }
 
@@ -163,6 +324,21 @@ impl BloomFilter {
 
         Self::new(k, m)
     }
+}
+
+impl<F: FilterHasher> BloomFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> BloomFilter<G> {
+        // Switch the hash function; only valid before inserting
+        BloomFilter {
+            bv: self.bv,
+            k: self.k,
+            n: self.n,
+            seeds: self.seeds,
+            seed: self.seed,
+            scheme: self.scheme,
+            hasher,
+        }
+    }
 
     pub fn n_hashes(&self) -> usize {
         self.k
@@ -172,18 +348,432 @@ impl BloomFilter {
         self.n
     }
 
-    fn hash_i<H: Hash>(&self, i: usize, item: &H) -> usize {
-        let s_i = i * 4;
-        let mut hasher = seahash::SeaHasher::with_seeds(self.seeds[s_i],
-                                                        self.seeds[s_i + 1],
-                                                        self.seeds[s_i + 2],
-                                                        self.seeds[s_i + 3]);
-        item.hash(&mut hasher);
-        hasher.finish() as usize % self.len()
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+
+    pub fn with_scheme(mut self, scheme: HashScheme) -> Self {
+        // Switch how probe hashes are derived; only valid before inserting
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn scheme(&self) -> HashScheme {
+        self.scheme
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn probes<'a, H: Hash>(&'a self, item: &'a H) -> Probes<'a, F, H> {
+        probes(&self.hasher, self.scheme, &self.seeds, self.k, item)
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), FilterError> {
+        // Same bits are set for the same items
+        if self.n == other.n && self.k == other.k && self.seeds == other.seeds && self.scheme == other.scheme {
+            Ok(())
+        } else {
+            Err(FilterError::Incompatible)
+        }
+    }
+
+    pub fn union(&mut self, other: &Self) -> Result<(), FilterError> {
+        // Filter of the union of both sets, exactly as if every item had
+        // been inserted here
+        self.check_compatible(other)?;
+        self.bv.union_with(&other.bv);
+        Ok(())
+    }
+
+    pub fn intersect(&mut self, other: &Self) -> Result<(), FilterError> {
+        // Keep the bits set in both. Contains every item of the
+        // intersection, but has a higher fp rate than a filter built from it.
+        self.check_compatible(other)?;
+        self.bv.intersect_with(&other.bv);
+        Ok(())
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.bv.count_ones()
+    }
+
+    pub fn fill_ratio(&self) -> f64 {
+        // Fraction of set bits
+        self.count_ones() as f64 / self.len() as f64
+    }
+
+    fn estimate_from_ones(&self, ones: usize) -> f64 {
+        // Swamidass & Baldi: n ~ -(m / k) ln(1 - X / m) for X set bits
+        let (m, k) = (self.len() as f64, self.k as f64);
+        -(m / k) * (1.0 - ones as f64 / m).ln()
+    }
+
+    pub fn estimate_len(&self) -> f64 {
+        // Estimated number of distinct items inserted; infinite once every
+        // bit is set
+        self.estimate_from_ones(self.count_ones())
+    }
+
+    pub fn estimate_union_len(&self, other: &Self) -> Result<f64, FilterError> {
+        let mut u = self.clone();
+        u.union(other)?;
+        Ok(u.estimate_len())
+    }
+
+    pub fn estimate_intersection_len(&self, other: &Self) -> Result<f64, FilterError> {
+        // |A| + |B| - |A u B|, more accurate than the fill of the bitwise and
+        let union = self.estimate_union_len(other)?;
+        Ok((self.estimate_len() + other.estimate_len() - union).max(0.0))
+    }
+
+    pub fn estimate_jaccard(&self, other: &Self) -> Result<f64, FilterError> {
+        // Estimated |A n B| / |A u B|
+        let union = self.estimate_union_len(other)?;
+        if union == 0.0 { return Ok(0.0) }
+        let inter = (self.estimate_len() + other.estimate_len() - union).max(0.0);
+        Ok(inter / union)
     }
 }
 
-fn bf_with_fpr_config(fpr: f32, n: usize) -> (usize, usize) {
+impl RegisterBlockedBloomFilter {
+    pub fn new(k: usize, n_blocks: usize, block_bits: usize) -> Self {
+        Self::with_seed(k, n_blocks, block_bits, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed(k: usize, n_blocks: usize, block_bits: usize, seed: u64) -> Self {
+        // block_bits is 64 (one register) or 512 (one cache line)
+        assert!(block_bits == 64 || block_bits == 512);
+        assert!(k > 0 && k <= block_bits);
+        let seeds = seeds_from(seed, 4);
+        let n_blocks = std::cmp::max(n_blocks, 1);
+        let block_words = block_bits / 64;
+
+        Self {
+            words: vec![0; n_blocks * block_words],
+            k,
+            nb: n_blocks,
+            block_words,
+            seeds,
+            seed,
+            hasher: SeaHash,
+        }
+    }
+
+    pub fn with_fpr(fpr: f32, n: usize, block_bits: usize) -> Self {
+        /* Create BF with `n` expected elements sized for fp rate `fpr`. Bits
+           are less evenly spread than in a BloomFilter, so the empirical fp
+           rate is higher, more so for 64 bit blocks. */
+        let (k, m) = bf_with_fpr_config(fpr, n);
+
+        Self::new(k, cdiv(m, block_bits), block_bits)
+    }
+}
+
+impl<F: FilterHasher> RegisterBlockedBloomFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> RegisterBlockedBloomFilter<G> {
+        // Switch the hash function; only valid before inserting
+        RegisterBlockedBloomFilter {
+            words: self.words,
+            k: self.k,
+            nb: self.nb,
+            block_words: self.block_words,
+            seeds: self.seeds,
+            seed: self.seed,
+            hasher,
+        }
+    }
+
+    fn masks<H: Hash>(&self, item: &H) -> (usize, [u64; 8]) {
+        // First word of item's block and the bits to test in each of its words
+        let h = seeded_hash(&self.hasher, &self.seeds, 0, item);
+        let block = (((h >> 32) * self.nb as u64) >> 32) as usize;
+        let h2 = h.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let (a, b) = (h & 0xffff_ffff, (h2 >> 32) | 1);
+        let bits = self.block_size() as u64 - 1;
+
+        let mut masks = [0_u64; 8];
+        for i in 0..self.k as u64 {
+            let pos = a.wrapping_add(i.wrapping_mul(b)) & bits;
+            masks[(pos / 64) as usize] |= 1 << (pos % 64);
+        }
+        (block * self.block_words, masks)
+    }
+
+    pub fn n_hashes(&self) -> usize {
+        self.k
+    }
+
+    pub fn len(&self) -> usize {
+        // Number of bits
+        self.words.len() * 64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn block_size(&self) -> usize {
+        // Block size in bits
+        self.block_words * 64
+    }
+
+    pub fn n_blocks(&self) -> usize {
+        self.nb
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+}
+
+impl PartitionedBloomFilter {
+    pub fn new(k: usize, n: usize) -> Self {
+        Self::with_seed(k, n, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed(k: usize, n: usize, seed: u64) -> Self {
+        // n bits, rounded up to k partitions of equal size
+        assert!(k > 0);
+        let seeds = seeds_from(seed, n_seeds(k));
+        let part = std::cmp::max(cdiv(n, k), 1);
+
+        Self {
+            bv: BitVec::new(k * part),
+            k,
+            part,
+            seeds,
+            seed,
+            scheme: HashScheme::default(),
+            hasher: SeaHash,
+        }
+    }
+
+    pub fn with_fpr(fpr: f32, n: usize) -> Self {
+        /* Create BF with fp rate `fpr` and `n` expected elements */
+        let (k, m) = bf_with_fpr_config(fpr, n);
+
+        Self::new(k, m)
+    }
+}
+
+impl<F: FilterHasher> PartitionedBloomFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> PartitionedBloomFilter<G> {
+        // Switch the hash function; only valid before inserting
+        PartitionedBloomFilter {
+            bv: self.bv,
+            k: self.k,
+            part: self.part,
+            seeds: self.seeds,
+            seed: self.seed,
+            scheme: self.scheme,
+            hasher,
+        }
+    }
+
+    pub fn n_hashes(&self) -> usize {
+        self.k
+    }
+
+    pub fn len(&self) -> usize {
+        self.bv.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bv.len() == 0
+    }
+
+    pub fn partition_len(&self) -> usize {
+        self.part
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+
+    pub fn with_scheme(mut self, scheme: HashScheme) -> Self {
+        // Switch how probe hashes are derived; only valid before inserting
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn scheme(&self) -> HashScheme {
+        self.scheme
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn probes<'a, H: Hash>(&'a self, item: &'a H) -> Probes<'a, F, H> {
+        probes(&self.hasher, self.scheme, &self.seeds, self.k, item)
+    }
+}
+
+impl CountingBloomFilter {
+    pub fn new(k: usize, n: usize, w: usize) -> Self {
+        Self::with_seed(k, n, w, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed(k: usize, n: usize, w: usize, seed: u64) -> Self {
+        // k hashes over n counters of w bits each
+        assert!(w > 0 && w <= 32);
+        let seeds = seeds_from(seed, n_seeds(k));
+
+        Self {
+            counters: IntVec::new(w, n),
+            k,
+            n,
+            seeds,
+            seed,
+            scheme: HashScheme::default(),
+            hasher: SeaHash,
+        }
+    }
+
+    pub fn with_fpr(fpr: f32, n: usize, w: usize) -> Self {
+        /* Create CBF with fp rate `fpr`, `n` expected elements and `w` bit counters */
+        let (k, m) = bf_with_fpr_config(fpr, n);
+
+        Self::new(k, m, w)
+    }
+}
+
+impl<F: FilterHasher> CountingBloomFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> CountingBloomFilter<G> {
+        // Switch the hash function; only valid before inserting
+        CountingBloomFilter {
+            counters: self.counters,
+            k: self.k,
+            n: self.n,
+            seeds: self.seeds,
+            seed: self.seed,
+            scheme: self.scheme,
+            hasher,
+        }
+    }
+
+    pub fn remove<H: Hash>(&mut self, item: &H) -> bool {
+        // Remove one copy of item. Returns false (and changes nothing) if
+        // item is definitely not in the filter. Removing an item that was
+        // never inserted may introduce false negatives. Probes of one item
+        // can share a counter, so counters already at zero are left alone.
+        if !self.query(item) { return false }
+        let (max, n) = (self.max_count(), self.len());
+        for h in probes(&self.hasher, self.scheme, &self.seeds, self.k, item) {
+            let h = reduce(h, n);
+            let c = self.counters.get_int(h);
+            if c > 0 && c < max {
+                self.counters.set_int(h, c - 1);
+            }
+        }
+        true
+    }
+
+    pub fn count<H: Hash>(&self, item: &H) -> u32 {
+        // Upper bound on the number of times item was inserted
+        self.probes(item).map(|h| self.counters.get_int(reduce(h, self.len()))).min().unwrap_or(0)
+    }
+
+    pub fn n_hashes(&self) -> usize {
+        self.k
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn counter_width(&self) -> usize {
+        self.counters.w_size()
+    }
+
+    fn max_count(&self) -> u32 {
+        ((1_u64 << self.counter_width()) - 1) as u32
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+
+    pub fn with_scheme(mut self, scheme: HashScheme) -> Self {
+        // Switch how probe hashes are derived; only valid before inserting
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn scheme(&self) -> HashScheme {
+        self.scheme
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn probes<'a, H: Hash>(&'a self, item: &'a H) -> Probes<'a, F, H> {
+        probes(&self.hasher, self.scheme, &self.seeds, self.k, item)
+    }
+}
+
+fn filter_space(name: &str, header: usize, bv: &BitVec, seeds: &[u64]) -> SpaceBreakdown {
+    SpaceBreakdown::node(name, vec![
+        SpaceBreakdown::leaf("header", header),
+        SpaceBreakdown::leaf("payload", bv.space().inline().bytes),
+        SpaceBreakdown::leaf("seeds", std::mem::size_of_val(seeds)),
+    ])
+}
+
+impl<F: FilterHasher> SpaceUsage for BloomFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        filter_space("BloomFilter", std::mem::size_of::<Self>(), &self.bv, &self.seeds)
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for BlockedBloomFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        filter_space("BlockedBloomFilter", std::mem::size_of::<Self>(), &self.bv, &self.seeds)
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for RegisterBlockedBloomFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("RegisterBlockedBloomFilter", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", std::mem::size_of_val::<[u64]>(&self.words)),
+            SpaceBreakdown::leaf("seeds", std::mem::size_of_val::<[u64]>(&self.seeds)),
+        ])
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for PartitionedBloomFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        filter_space("PartitionedBloomFilter", std::mem::size_of::<Self>(), &self.bv, &self.seeds)
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for CountingBloomFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("CountingBloomFilter", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", self.counters.space().inline().bytes),
+            SpaceBreakdown::leaf("seeds", std::mem::size_of_val::<[u64]>(&self.seeds)),
+        ])
+    }
+}
+
+pub fn bf_with_fpr_config(fpr: f32, n: usize) -> (usize, usize) {
     // 1) Calculate optimal size:
     let m = -1.0 * n as f32 * fpr.ln() / (LN_2 * LN_2);
 
@@ -200,6 +790,7 @@ fn bf_with_fpr_config(fpr: f32, n: usize) -> (usize, usize) {
 mod bf_tests {
     use crate::bloom_filter::*;
     use crate::bloom_filter::MQ;
+    use crate::hashing::{CanonicalKmer, HashScheme, MultiplyShift, RawBytes};
 
     #[test]
     fn new() {
@@ -241,6 +832,225 @@ mod bf_tests {
         assert!(!bf.query(&s));
     }
 
+    #[test]
+    fn counting() {
+        let mut bf = CountingBloomFilter::with_fpr(0.01, 1000, 4);
+        assert_eq!(bf.counter_width(), 4);
+        for i in 0..1000 {
+            bf.insert(&i);
+        }
+        for i in 0..1000 {
+            assert!(bf.query(&i));
+        }
+        for i in 0..500 {
+            assert!(bf.remove(&i));
+        }
+        for i in 500..1000 {
+            assert!(bf.query(&i));
+        }
+        let fp = (0..500).filter(|i| bf.query(i)).count();
+        assert!(fp < 50);
+        assert!(!bf.remove(&"never inserted"));
+    }
+
+    #[test]
+    fn counting_saturates() {
+        let mut bf = CountingBloomFilter::new(3, 100, 2);
+        for _ in 0..10 {
+            bf.insert(&"x");
+        }
+        assert_eq!(bf.count(&"x"), 3);
+        // Saturated counters are never decremented
+        for _ in 0..10 {
+            assert!(bf.remove(&"x"));
+        }
+        assert!(bf.query(&"x"));
+
+        let mut bf = CountingBloomFilter::new(3, 100, 4);
+        bf.insert(&"y");
+        bf.insert(&"y");
+        assert_eq!(bf.count(&"y"), 2);
+        bf.remove(&"y");
+        assert!(bf.query(&"y"));
+        bf.remove(&"y");
+        assert!(!bf.query(&"y"));
+    }
+
+    #[test]
+    fn counting_remove_false_positive() {
+        // With 3 probes over 2 counters every item repeats a counter, so
+        // removing false positives decrements counters more often than they
+        // were incremented; they must stop at zero rather than wrap
+        for &scheme in &[HashScheme::Seeded, HashScheme::DoubleHashing] {
+            let mut cbf = CountingBloomFilter::with_seed(3, 2, 4, 5).with_scheme(scheme);
+            cbf.insert(&0);
+            for i in 1..100 {
+                cbf.remove(&i);
+                assert!(cbf.count(&i) <= 1);
+            }
+            assert_eq!(cbf.count(&0), 0);
+        }
+    }
+
+    #[test]
+    fn register_blocked() {
+        for &b in &[64, 512] {
+            let mut bf = RegisterBlockedBloomFilter::with_fpr(0.01, 1000, b);
+            assert_eq!(bf.block_size(), b);
+            assert_eq!(bf.len(), bf.n_blocks() * b);
+            for i in 0..1000 {
+                bf.insert(&i);
+            }
+            for i in 0..1000 {
+                assert!(bf.query(&i));
+            }
+            let fp = (1000..11000).filter(|i| bf.query(i)).count();
+            assert!(fp < 500, "{} false positives", fp);
+        }
+    }
+
+    #[test]
+    fn partitioned() {
+        let mut bf = PartitionedBloomFilter::with_fpr(0.01, 1000);
+        assert_eq!(bf.len(), bf.n_hashes() * bf.partition_len());
+        for i in 0..1000 {
+            bf.insert(&i);
+        }
+        for i in 0..1000 {
+            assert!(bf.query(&i));
+        }
+        let fp = (1000..11000).filter(|i| bf.query(i)).count();
+        assert!(fp < 200, "{} false positives", fp);
+    }
+
+    #[test]
+    fn seeded_and_bytes() {
+        let build = || {
+            let mut bf = BloomFilter::with_seed(4, 1000, 42).with_scheme(HashScheme::DoubleHashing);
+            for i in 0..100_u32 {
+                bf.insert_bytes(&i.to_le_bytes());
+            }
+            bf
+        };
+        let (bf, bf2) = (build(), build());
+        let encoded = bincode::serialize(&bf).unwrap();
+        assert_eq!(encoded, bincode::serialize(&bf2).unwrap());
+
+        // Seed and scheme travel with the filter
+        let bf: BloomFilter = bincode::deserialize(&encoded).unwrap();
+        assert_eq!(bf.seed(), 42);
+        assert_eq!(bf.scheme(), HashScheme::DoubleHashing);
+        for i in 0..100_u32 {
+            assert!(bf.query_bytes(&i.to_le_bytes()));
+        }
+
+        let mut cbf = CountingBloomFilter::with_seed(3, 100, 4, 7);
+        cbf.insert_bytes(b"ACGT");
+        assert!(cbf.query(&RawBytes(b"ACGT")));
+        let rbbf = RegisterBlockedBloomFilter::with_seed(3, 10, 512, 7);
+        assert_eq!(rbbf.seed(), 7);
+    }
+
+    #[test]
+    fn hashers() {
+        let mut bf = BloomFilter::with_fpr(0.01, 1000).with_hasher(MultiplyShift);
+        let mut pbf = PartitionedBloomFilter::with_fpr(0.01, 1000).with_hasher(MultiplyShift)
+                                                                  .with_scheme(HashScheme::DoubleHashing);
+        for i in 0..1000_u64 {
+            bf.insert(&(i * 7919));
+            pbf.insert(&(i * 7919));
+        }
+        for i in 0..1000_u64 {
+            assert!(bf.query(&(i * 7919)) && pbf.query(&(i * 7919)));
+        }
+        let fp = (0..10000_u64).filter(|i| i % 7919 != 0 && bf.query(i)).count();
+        assert!(fp < 200, "{} false positives", fp);
+
+        // The hasher is recorded, so it cannot be read back as another one
+        let encoded = bincode::serialize(&bf).unwrap();
+        assert!(bincode::deserialize::<BloomFilter>(&encoded).is_err());
+        let bf: BloomFilter<MultiplyShift> = bincode::deserialize(&encoded).unwrap();
+        assert!(bf.query(&7919_u64));
+
+        // A k-mer is found through either strand
+        let mut bbf = BlockedBloomFilter::with_seed(4, 16, 64, 3).with_hasher(CanonicalKmer);
+        let mut rbbf = RegisterBlockedBloomFilter::with_seed(4, 16, 512, 3).with_hasher(CanonicalKmer);
+        bbf.insert(&"GATTACA");
+        rbbf.insert_bytes(b"GATTACA");
+        assert!(bbf.query(&"TGTAATC"));
+        assert!(rbbf.query_bytes(b"TGTAATC"));
+        let mut cbf = CountingBloomFilter::new(3, 100, 4).with_hasher(CanonicalKmer);
+        cbf.insert(&"AACG");
+        assert!(cbf.remove(&"CGTT"));
+        assert!(!cbf.query(&"AACG"));
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut a = BloomFilter::with_seed(7, 20000, 5);
+        let mut b = BloomFilter::with_seed(7, 20000, 5);
+        for i in 0..1000 {
+            a.insert(&i);
+        }
+        for i in 500..1500 {
+            b.insert(&i);
+        }
+        let est = a.estimate_len();
+        assert!((est - 1000.0).abs() < 50.0, "{}", est);
+        assert!(a.fill_ratio() > 0.0 && a.fill_ratio() < 1.0);
+
+        let union = a.estimate_union_len(&b).unwrap();
+        assert!((union - 1500.0).abs() < 75.0, "{}", union);
+        let inter = a.estimate_intersection_len(&b).unwrap();
+        assert!((inter - 500.0).abs() < 75.0, "{}", inter);
+        let j = a.estimate_jaccard(&b).unwrap();
+        assert!((j - 1.0 / 3.0).abs() < 0.05, "{}", j);
+
+        let mut u = a.clone();
+        u.union(&b).unwrap();
+        let mut i = a.clone();
+        i.intersect(&b).unwrap();
+        for x in 0..1500 {
+            assert!(u.query(&x));
+        }
+        for x in 500..1000 {
+            assert!(i.query(&x));
+        }
+        assert!(i.count_ones() <= a.count_ones());
+
+        // Different seeds, k or size
+        let c = BloomFilter::with_seed(7, 20000, 6);
+        assert_eq!(a.clone().union(&c), Err(FilterError::Incompatible));
+        let c = BloomFilter::with_seed(6, 20000, 5);
+        assert_eq!(a.clone().intersect(&c), Err(FilterError::Incompatible));
+        let c = BloomFilter::with_seed(7, 20001, 5);
+        assert!(a.estimate_jaccard(&c).is_err());
+    }
+
+    #[test]
+    fn double_hashing() {
+        let scheme = HashScheme::DoubleHashing;
+        let mut bf = BloomFilter::with_fpr(0.01, 1000).with_scheme(scheme);
+        let mut bbf = BlockedBloomFilter::with_fpr(0.01, 1000, 64).with_scheme(scheme);
+        let mut pbf = PartitionedBloomFilter::with_fpr(0.01, 1000).with_scheme(scheme);
+        let mut cbf = CountingBloomFilter::with_fpr(0.01, 1000, 4).with_scheme(scheme);
+        assert_eq!(bf.scheme(), scheme);
+        for i in 0..1000 {
+            bf.insert(&i);
+            bbf.insert(&i);
+            pbf.insert(&i);
+            cbf.insert(&i);
+        }
+        for i in 0..1000 {
+            assert!(bf.query(&i) && bbf.query(&i) && pbf.query(&i) && cbf.query(&i));
+        }
+        let fp = (1000..11000).filter(|i| bf.query(i)).count();
+        assert!(fp < 200, "{} false positives", fp);
+        let fp = (1000..11000).filter(|i| pbf.query(i)).count();
+        assert!(fp < 200, "{} false positives", fp);
+        assert!(cbf.remove(&7));
+    }
+
     #[test]
     fn bf_from_fpr() {
         // Sanity check for FPR
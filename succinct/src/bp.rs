@@ -0,0 +1,600 @@
+use super::bv::{BitVec, IntVec};
+use super::rank_select::RankSupport;
+use super::math::{cdiv, clog};
+use serde::{Serialize, Deserialize};
+use std::cmp::{max, min};
+
+// For every byte (first bit most significant, 1 = +1, 0 = -1): the minimum
+// and maximum prefix excess after 1..=8 bits, the offset of the leftmost
+// minimum, and the total excess.
+static BYTE_EXCESS: [(i8, i8, u8, i8); 256] = byte_excess_table();
+
+const fn byte_excess_table() -> [(i8, i8, u8, i8); 256] {
+    let mut table = [(0_i8, 0_i8, 0_u8, 0_i8); 256];
+    let mut v = 0;
+    while v < 256 {
+        let mut e = 0_i8;
+        let mut min_e = i8::MAX;
+        let mut max_e = i8::MIN;
+        let mut pos = 0_u8;
+        let mut t = 0;
+        while t < 8 {
+            if (v >> (7 - t)) & 1 == 1 { e += 1 } else { e -= 1 }
+            if e < min_e {
+                min_e = e;
+                pos = t as u8;
+            }
+            if e > max_e { max_e = e }
+            t += 1;
+        }
+        table[v] = (min_e, max_e, pos, e);
+        v += 1;
+    }
+    table
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalancedParens {
+    // Balanced parentheses ('(' = 1, ')' = 0) with a range min-max tree.
+    // Navigation follows Sadakane and Navarro. "Fully-Functional Succinct
+    // Trees": the bits are cut into blocks of b bits, and a complete binary
+    // tree over the blocks stores the min and max excess of every subtree.
+    // excess(i) is the number of '(' minus the number of ')' in [0, i].
+    //
+    // Blocks of b ~ log^2 n bits are cut into sub-blocks of s = O(log n)
+    // bits that store their min and max excess relative to the excess
+    // before them, in O(log log n) bits each. A leaf is searched by skipping
+    // sub-blocks that cannot hold the target and scanning the rest a byte at
+    // a time through BYTE_EXCESS, so queries take O(log n) time and the
+    // whole structure n + o(n) bits.
+    n: usize,
+    rs: RankSupport,
+    b: usize,
+    n_blocks: usize,
+    size: usize,   // leaves in the (complete) min-max tree
+    min: IntVec,   // heap order, root at 1
+    max: IntVec,
+    s: usize,      // sub-block size in bits, a multiple of 8 dividing b
+    sub_min: IntVec, // min excess in each sub-block relative to the excess before it, plus s
+    sub_max: IntVec,
+}
+
+impl BalancedParens {
+    pub fn new(bv: BitVec) -> Self {
+        let n = bv.len();
+        let lg = max(clog(n + 1), 8);
+        let s = 8 * lg;
+        let b = s * cdiv(lg, 8);
+        let n_blocks = cdiv(n, b);
+        let size = n_blocks.next_power_of_two();
+
+        // Empty nodes get min > max so they never contain an excess
+        let w = clog(n + 2);
+        let empty = (1_u32 << w) - 1;
+        let mut min_e = IntVec::new(w, 2 * size);
+        let mut max_e = IntVec::new(w, 2 * size);
+        for v in 0..2 * size {
+            min_e.set_int(v, empty);
+        }
+
+        let mut e: isize = 0;
+        for k in 0..n_blocks {
+            let (mut lo, mut hi) = (isize::MAX, 0);
+            for i in k * b..min((k + 1) * b, n) {
+                e += if bv.get(i) { 1 } else { -1 };
+                assert!(e >= 0, "unbalanced parentheses");
+                lo = min(lo, e);
+                hi = max(hi, e);
+            }
+            min_e.set_int(size + k, lo as u32);
+            max_e.set_int(size + k, hi as u32);
+        }
+        assert_eq!(e, 0, "unbalanced parentheses");
+
+        let n_sub = cdiv(n, s);
+        let ws = clog(2 * s + 1);
+        let mut sub_min = IntVec::new(ws, n_sub);
+        let mut sub_max = IntVec::new(ws, n_sub);
+        for t in 0..n_sub {
+            let (mut lo, mut hi, mut e) = (isize::MAX, isize::MIN, 0);
+            for i in t * s..min((t + 1) * s, n) {
+                e += if bv.get(i) { 1 } else { -1 };
+                lo = min(lo, e);
+                hi = max(hi, e);
+            }
+            sub_min.set_int(t, (lo + s as isize) as u32);
+            sub_max.set_int(t, (hi + s as isize) as u32);
+        }
+
+        for v in (1..size).rev() {
+            min_e.set_int(v, min(min_e.get_int(2 * v), min_e.get_int(2 * v + 1)));
+            max_e.set_int(v, max(max_e.get_int(2 * v), max_e.get_int(2 * v + 1)));
+        }
+
+        // RankSupport needs at least one bit; the empty sequence gets a
+        // padding bit past len()
+        let bv = if n == 0 { BitVec::new(1) } else { bv };
+        Self {
+            n,
+            rs: RankSupport::new(bv),
+            b,
+            n_blocks,
+            size,
+            min: min_e,
+            max: max_e,
+            s,
+            sub_min,
+            sub_max,
+        }
+    }
+
+    pub fn from_parens(s: &str) -> Self {
+        // Parse a string of '(' and ')'
+        let mut bv = BitVec::new(s.len());
+        for (i, c) in s.chars().enumerate() {
+            assert!(c == '(' || c == ')');
+            bv.set(i, c == '(');
+        }
+        Self::new(bv)
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_open(&self, i: usize) -> bool {
+        self.rs.get(i)
+    }
+
+    pub fn excess(&self, i: usize) -> usize {
+        2 * self.rs.rank1(i) - (i + 1)
+    }
+
+    pub fn rank_open(&self, i: usize) -> usize {
+        // Number of '(' in [0, i]
+        self.rs.rank1(i)
+    }
+
+    pub fn rank_close(&self, i: usize) -> usize {
+        // Number of ')' in [0, i]
+        self.rs.rank0(i)
+    }
+
+    pub fn select_open(&self, r: usize) -> Option<usize> {
+        // Position of the r-th '(' (1-based)
+        self.rs.select1(r).filter(|&p| p < self.n)
+    }
+
+    pub fn select_close(&self, r: usize) -> Option<usize> {
+        // Position of the r-th ')' (1-based)
+        self.rs.select0(r).filter(|&p| p < self.n)
+    }
+
+    pub fn find_close(&self, i: usize) -> usize {
+        // Matching ')' of the '(' at i
+        assert!(self.is_open(i));
+        self.fwd_search(i, self.excess(i) - 1).unwrap()
+    }
+
+    pub fn find_open(&self, i: usize) -> usize {
+        // Matching '(' of the ')' at i
+        assert!(!self.is_open(i));
+        self.bwd_search(i, self.excess(i)).map_or(0, |j| j + 1)
+    }
+
+    pub fn enclose(&self, i: usize) -> Option<usize> {
+        // '(' of the tightest pair strictly enclosing the '(' at i
+        assert!(self.is_open(i));
+        let e = self.excess(i);
+        if e < 2 { return None }
+        Some(self.bwd_search(i, e - 2).map_or(0, |j| j + 1))
+    }
+
+    pub fn rmq(&self, i: usize, j: usize) -> usize {
+        // Leftmost position of the minimum excess in [i, j]
+        assert!(i <= j && j < self.len());
+        let bi = i / self.b;
+        let bj = j / self.b;
+        if bj <= bi + 1 {
+            return self.min_range(i, j, (i, self.excess(i))).0;
+        }
+
+        let mut best = self.min_range(i, (bi + 1) * self.b - 1, (i, self.excess(i)));
+
+        // Canonical node with the smallest min covering blocks (bi, bj)
+        let mut node = None;
+        for v in self.cover(bi + 1, bj - 1) {
+            if node.is_none_or(|u| self.min.get_int(v) < self.min.get_int(u)) {
+                node = Some(v);
+            }
+        }
+        let mut v = node.unwrap();
+        let m = self.min.get_int(v) as usize;
+        if m < best.1 {
+            while v < self.size {
+                v = if self.min.get_int(2 * v) as usize == m { 2 * v } else { 2 * v + 1 };
+            }
+            let start = (v - self.size) * self.b;
+            best = self.min_range(start, start + self.b - 1, (start, usize::MAX));
+        }
+
+        let start = bj * self.b;
+        self.min_range(start, j, best).0
+    }
+
+    pub fn fwd_search(&self, i: usize, target: usize) -> Option<usize> {
+        // Smallest j > i with excess(j) == target
+        let n = self.len();
+        let bi = i / self.b;
+        let j = self.fwd_range(i + 1, min((bi + 1) * self.b, n), target);
+        if j.is_some() { return j }
+
+        // Climb until a right sibling contains target, then descend
+        let mut v = self.size + bi;
+        loop {
+            if v == 1 { return None }
+            if v.is_multiple_of(2) && self.contains(v + 1, target) {
+                v += 1;
+                break;
+            }
+            v /= 2;
+        }
+        while v < self.size {
+            v = if self.contains(2 * v, target) { 2 * v } else { 2 * v + 1 };
+        }
+
+        let start = (v - self.size) * self.b;
+        let j = self.fwd_range(start, min(start + self.b, n), target);
+        assert!(j.is_some());
+        j
+    }
+
+    pub fn bwd_search(&self, i: usize, target: usize) -> Option<usize> {
+        // Largest j < i with excess(j) == target
+        let bi = i / self.b;
+        let j = self.bwd_range(bi * self.b, i, target);
+        if j.is_some() { return j }
+
+        // Climb until a left sibling contains target, then descend
+        let mut v = self.size + bi;
+        loop {
+            if v == 1 { return None }
+            if !v.is_multiple_of(2) && self.contains(v - 1, target) {
+                v -= 1;
+                break;
+            }
+            v /= 2;
+        }
+        while v < self.size {
+            v = if self.contains(2 * v + 1, target) { 2 * v + 1 } else { 2 * v };
+        }
+
+        let start = (v - self.size) * self.b;
+        let j = self.bwd_range(start, min(start + self.b, self.len()), target);
+        assert!(j.is_some());
+        j
+    }
+
+    fn step(&self, e: usize, j: usize) -> usize {
+        // excess(j) from excess(j - 1)
+        if self.rs.get(j) { e + 1 } else { e - 1 }
+    }
+
+    fn step_back(&self, e: usize, j: usize) -> usize {
+        // excess(j - 1) from excess(j)
+        if self.rs.get(j) { e - 1 } else { e + 1 }
+    }
+
+    fn contains(&self, v: usize, target: usize) -> bool {
+        let t = target as u32;
+        self.min.get_int(v) <= t && t <= self.max.get_int(v)
+    }
+
+    fn excess_before(&self, i: usize) -> usize {
+        // excess(i - 1), with excess(-1) = 0
+        if i == 0 { 0 } else { self.excess(i - 1) }
+    }
+
+    fn sub_contains(&self, t: usize, target: usize) -> bool {
+        // Whether sub-block t may hold an excess equal to target
+        let lo = self.excess_before(t * self.s) + self.sub_min.get_int(t) as usize - self.s;
+        let hi = self.excess_before(t * self.s) + self.sub_max.get_int(t) as usize - self.s;
+        lo <= target && target <= hi
+    }
+
+    fn fwd_range(&self, from: usize, to: usize, target: usize) -> Option<usize> {
+        // First j in [from, to) with excess(j) == target, skipping sub-blocks
+        let mut q = from;
+        while q < to {
+            let t = q / self.s;
+            let end = min((t + 1) * self.s, to);
+            if self.sub_contains(t, target) {
+                let j = self.fwd_scan(q, end, target);
+                if j.is_some() { return j }
+            }
+            q = end;
+        }
+        None
+    }
+
+    fn bwd_range(&self, from: usize, to: usize, target: usize) -> Option<usize> {
+        // Last j in [from, to) with excess(j) == target, skipping sub-blocks
+        let mut q = to;
+        while q > from {
+            let t = (q - 1) / self.s;
+            let start = max(t * self.s, from);
+            if self.sub_contains(t, target) {
+                let j = self.bwd_scan(start, q, target);
+                if j.is_some() { return j }
+            }
+            q = start;
+        }
+        None
+    }
+
+    fn fwd_scan(&self, from: usize, to: usize, target: usize) -> Option<usize> {
+        // First j in [from, to) with excess(j) == target, whole bytes
+        // through BYTE_EXCESS and the remainder bit by bit
+        let mut e = self.excess_before(from);
+        let mut q = from;
+        while q < to {
+            if q.is_multiple_of(8) && q + 8 <= to {
+                let (lo, hi, _, total) = BYTE_EXCESS[self.rs.bits().get_int(q, 8) as usize];
+                let d = target as isize - e as isize;
+                if d < lo as isize || d > hi as isize {
+                    e = (e as isize + total as isize) as usize;
+                    q += 8;
+                    continue;
+                }
+            }
+            e = self.step(e, q);
+            if e == target { return Some(q) }
+            q += 1;
+        }
+        None
+    }
+
+    fn bwd_scan(&self, from: usize, to: usize, target: usize) -> Option<usize> {
+        // Last j in [from, to) with excess(j) == target, whole bytes
+        // through BYTE_EXCESS and the remainder bit by bit
+        let mut e = self.excess(to - 1);
+        let mut q = to;
+        while q > from {
+            if q.is_multiple_of(8) && q >= from + 8 {
+                let (lo, hi, _, total) = BYTE_EXCESS[self.rs.bits().get_int(q - 8, 8) as usize];
+                let before = e as isize - total as isize;
+                let d = target as isize - before;
+                if d < lo as isize || d > hi as isize {
+                    e = before as usize;
+                    q -= 8;
+                    continue;
+                }
+            }
+            if e == target { return Some(q - 1) }
+            e = self.step_back(e, q - 1);
+            q -= 1;
+        }
+        None
+    }
+
+    fn min_range(&self, i: usize, j: usize, best: (usize, usize)) -> (usize, usize) {
+        // Leftmost minimum excess in [i, j], or best if it is smaller. Only
+        // the sub-block with the smallest min between the ends is scanned.
+        let j = min(j, self.len() - 1);
+        let (ti, tj) = (i / self.s, j / self.s);
+        if tj <= ti + 1 {
+            return self.scan_min(i, j, best);
+        }
+        let mut best = self.scan_min(i, (ti + 1) * self.s - 1, best);
+
+        let mut cand: Option<(usize, usize)> = None;
+        for t in ti + 1..tj {
+            let m = self.excess_before(t * self.s) + self.sub_min.get_int(t) as usize - self.s;
+            if cand.is_none_or(|(_, cm)| m < cm) {
+                cand = Some((t, m));
+            }
+        }
+        let (t, m) = cand.unwrap();
+        if m < best.1 {
+            best = self.scan_min(t * self.s, (t + 1) * self.s - 1, best);
+        }
+        self.scan_min(tj * self.s, j, best)
+    }
+
+    fn scan_min(&self, i: usize, j: usize, best: (usize, usize)) -> (usize, usize) {
+        // Leftmost minimum excess in [i, j], or best if it is smaller, whole
+        // bytes through BYTE_EXCESS and the remainder bit by bit
+        let mut e = self.excess_before(i);
+        let mut best = best;
+        let mut q = i;
+        while q <= j {
+            if q.is_multiple_of(8) && q + 8 <= j + 1 {
+                let (lo, _, pos, total) = BYTE_EXCESS[self.rs.bits().get_int(q, 8) as usize];
+                let m = (e as isize + lo as isize) as usize;
+                let k = q + pos as usize;
+                if m < best.1 || (m == best.1 && k < best.0) {
+                    best = (k, m);
+                }
+                e = (e as isize + total as isize) as usize;
+                q += 8;
+                continue;
+            }
+            e = self.step(e, q);
+            if e < best.1 || (e == best.1 && q < best.0) {
+                best = (q, e);
+            }
+            q += 1;
+        }
+        best
+    }
+
+    fn cover(&self, bl: usize, br: usize) -> Vec<usize> {
+        // Canonical min-max tree nodes covering blocks [bl, br], left to right
+        let mut left = vec![];
+        let mut right = vec![];
+        let mut l = bl + self.size;
+        let mut r = br + self.size + 1;
+        while l < r {
+            if !l.is_multiple_of(2) {
+                left.push(l);
+                l += 1;
+            }
+            if !r.is_multiple_of(2) {
+                r -= 1;
+                right.push(r);
+            }
+            l /= 2;
+            r /= 2;
+        }
+        left.extend(right.iter().rev());
+        left
+    }
+
+    pub fn n_blocks(&self) -> usize {
+        self.n_blocks
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.rs.size_of();
+        size += self.min.size_of();
+        size += self.max.size_of();
+        size += self.sub_min.size_of();
+        size += self.sub_max.size_of();
+        size
+    }
+}
+
+#[cfg(test)]
+mod bp_tests {
+    use crate::bp::*;
+    use rand::Rng;
+
+    fn random_parens(n_pairs: usize) -> String {
+        let mut rng = rand::thread_rng();
+        let mut s = String::new();
+        let (mut open, mut close) = (n_pairs, n_pairs);
+        while close > 0 {
+            let depth = close - open;
+            if open > 0 && (depth == 0 || rng.gen::<bool>()) {
+                s.push('(');
+                open -= 1;
+            } else {
+                s.push(')');
+                close -= 1;
+            }
+        }
+        s
+    }
+
+    fn naive_close(s: &[u8], i: usize) -> usize {
+        let mut d = 0;
+        for (j, &c) in s.iter().enumerate().skip(i) {
+            d += if c == b'(' { 1 } else { -1 };
+            if d == 0 { return j }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn small() {
+        let bp = BalancedParens::from_parens("(()(()))()");
+        assert_eq!(bp.find_close(0), 7);
+        assert_eq!(bp.find_close(1), 2);
+        assert_eq!(bp.find_close(4), 5);
+        assert_eq!(bp.find_open(7), 0);
+        assert_eq!(bp.find_open(6), 3);
+        assert_eq!(bp.find_open(9), 8);
+        assert_eq!(bp.enclose(4), Some(3));
+        assert_eq!(bp.enclose(3), Some(0));
+        assert_eq!(bp.enclose(0), None);
+        assert_eq!(bp.enclose(8), None);
+        assert_eq!(bp.excess(4), 3);
+        assert_eq!(bp.rmq(1, 6), 2);
+        assert_eq!(bp.rank_open(4), 4);
+        assert_eq!(bp.select_open(5), Some(8));
+        assert_eq!(bp.select_close(2), Some(5));
+    }
+
+    #[test]
+    fn empty() {
+        let bp = BalancedParens::from_parens("");
+        assert!(bp.is_empty());
+        assert_eq!(bp.n_blocks(), 0);
+        assert_eq!(bp.select_open(1), None);
+        assert_eq!(bp.select_close(1), None);
+    }
+
+    #[test]
+    fn fuzz() {
+        let mut rng = rand::thread_rng();
+        for &n_pairs in &[1, 10, 100, 2000] {
+            let s = random_parens(n_pairs);
+            let bytes = s.as_bytes();
+            let bp = BalancedParens::from_parens(&s);
+            let mut excess = vec![0; bytes.len()];
+            let mut e = 0;
+            for (i, &c) in bytes.iter().enumerate() {
+                e = if c == b'(' { e + 1 } else { e - 1 };
+                excess[i] = e;
+            }
+
+            for (i, &c) in bytes.iter().enumerate() {
+                assert_eq!(bp.excess(i), excess[i]);
+                if c == b'(' {
+                    let j = naive_close(bytes, i);
+                    assert_eq!(bp.find_close(i), j);
+                    assert_eq!(bp.find_open(j), i);
+                    let parent = (0..i).rev().find(|&p| bytes[p] == b'(' && naive_close(bytes, p) > i);
+                    assert_eq!(bp.enclose(i), parent);
+                }
+            }
+
+            for _ in 0..500 {
+                let i = rng.gen_range(0, bytes.len());
+                let j = rng.gen_range(i, bytes.len());
+                let m = *excess[i..j + 1].iter().min().unwrap();
+                let p = (i..j + 1).find(|&k| excess[k] == m).unwrap();
+                assert_eq!(bp.rmq(i, j), p);
+            }
+        }
+    }
+
+    #[test]
+    fn search() {
+        // Arbitrary targets, above and below the current excess, over
+        // enough bits to span many sub-blocks and blocks
+        let mut rng = rand::thread_rng();
+        let s = random_parens(20_000);
+        let bp = BalancedParens::from_parens(&s);
+        assert!(bp.n_blocks() > 16);
+        let mut excess = vec![0; s.len()];
+        let mut e = 0;
+        for (i, c) in s.bytes().enumerate() {
+            e = if c == b'(' { e + 1 } else { e - 1 };
+            excess[i] = e;
+        }
+        for _ in 0..2000 {
+            let i = rng.gen_range(0, s.len());
+            let target = rng.gen_range(0, excess[i] + 20);
+            assert_eq!(bp.fwd_search(i, target), (i + 1..s.len()).find(|&j| excess[j] == target));
+            assert_eq!(bp.bwd_search(i, target), (0..i).rev().find(|&j| excess[j] == target));
+            let j = rng.gen_range(i, s.len());
+            let m = *excess[i..j + 1].iter().min().unwrap();
+            assert_eq!(bp.rmq(i, j), (i..j + 1).find(|&k| excess[k] == m).unwrap());
+        }
+    }
+
+    #[test]
+    fn byte_table() {
+        assert_eq!(BYTE_EXCESS[0xff], (1, 8, 0, 8));
+        assert_eq!(BYTE_EXCESS[0x00], (-8, -1, 7, -8));
+        assert_eq!(BYTE_EXCESS[0b1010_1010], (0, 1, 1, 0));
+        assert_eq!(BYTE_EXCESS[0b0110_0011], (-2, 1, 5, 0));
+    }
+}
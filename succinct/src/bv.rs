@@ -1,5 +1,6 @@
 use serde::{Serialize, Deserialize};
 use super::math;
+use super::space::{SpaceBreakdown, SpaceUsage};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BitVec {
@@ -98,6 +99,27 @@ impl BitVec {
         self.n
     }
 
+    pub fn count_ones(&self) -> usize {
+        // Number of set bits (bits past n are always 0)
+        self.blocks.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    pub fn union_with(&mut self, other: &BitVec) {
+        // self |= other
+        assert_eq!(self.len(), other.len());
+        for (a, b) in self.blocks.iter_mut().zip(other.blocks.iter()) {
+            *a |= b;
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: &BitVec) {
+        // self &= other
+        assert_eq!(self.len(), other.len());
+        for (a, b) in self.blocks.iter_mut().zip(other.blocks.iter()) {
+            *a &= b;
+        }
+    }
+
     fn val_fits(v: u32, word_size: usize) -> bool {
         // hack to allow shifting by more than 32 bits
         (v as u64 >> word_size) == 0u64 
@@ -195,9 +217,14 @@ impl IntVec {
         self.word_size
     }
 
+    pub fn bits(&self) -> &BitVec {
+        // Packed words, element i at [i * w, (i + 1) * w)
+        &self.bv
+    }
+
     pub fn size_of(&self) -> usize {
         // Size of IntVector in bytes
-        std::mem::size_of::<Self>() + self.bv.size_of()
+        self.space().bytes
     }
 
     pub fn from_vec(elems: &Vec<u32>, w: usize) -> Self {
@@ -218,6 +245,24 @@ impl IntVec {
     }
 }
 
+impl SpaceUsage for BitVec {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("BitVec", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", std::mem::size_of_val::<[u32]>(&*self.blocks)),
+        ])
+    }
+}
+
+impl SpaceUsage for IntVec {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("IntVec", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", self.bv.space().inline().bytes),
+        ])
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::bv::*;
@@ -285,6 +330,22 @@ mod tests {
         assert_eq!(v.get_int(61, 5), 17);
     }
 
+    #[test]
+    fn set_ops() {
+        let mut a = BitVec::new(70);
+        let mut b = BitVec::new(70);
+        a.set(0, true);
+        a.set(69, true);
+        b.set(69, true);
+        b.set(33, true);
+        let mut c = a.clone();
+        c.union_with(&b);
+        assert_eq!(c.count_ones(), 3);
+        a.intersect_with(&b);
+        assert_eq!(a.count_ones(), 1);
+        assert!(a.get(69));
+    }
+
     #[test]
     fn fuzz_get_set(){
         let mut v = IntVec::new(7, 100);
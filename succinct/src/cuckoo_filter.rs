@@ -0,0 +1,312 @@
+use super::bv::IntVec;
+use super::bloom_filter::{FilterError, Query, MQ};
+use super::hashing::{mix64, reduce, seeded_hash, seeds_from, FilterHasher, SeaHash};
+use super::math::{clog, exp2};
+use super::space::{SpaceBreakdown, SpaceUsage};
+
+use rand::Rng;
+use std::cmp::max;
+use std::hash::Hash;
+use serde::{Serialize, Deserialize};
+
+// Evictions tried before an insert gives up
+const MAX_KICKS: usize = 500;
+// Load factor used to size filters from an expected number of items
+const LOAD: f32 = 0.95;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CuckooFilter<F = SeaHash> {
+    // Cuckoo filter (Fan et al.) with partial-key cuckoo hashing. Bucket i
+    // holds slots [i * b, (i + 1) * b) of `table`; fingerprints are f bit
+    // values in [1, 2^f), 0 marks an empty slot. The alternate bucket of a
+    // fingerprint is its bucket xor the hash of the fingerprint, so the
+    // number of buckets is a power of two. Items are hashed with F; the
+    // alternate bucket always uses SeaHash of the fingerprint.
+    table: IntVec,
+    n_buckets: usize,
+    b: usize,
+    n_items: usize,
+    seeds: Vec<u64>,
+    seed: u64,
+    hasher: F,
+}
+
+impl<F: FilterHasher> MQ for CuckooFilter<F> {
+    fn insert<H: Hash>(&mut self, item: &H) {
+        self.try_insert(item).expect("cuckoo filter is full");
+    }
+}
+
+impl<F: FilterHasher> Query for CuckooFilter<F> {
+    fn query<H: Hash>(&self, item: &H) -> bool {
+        let (fp, i1, i2) = self.locate(item);
+        self.find(i1, fp).is_some() || self.find(i2, fp).is_some()
+    }
+}
+
+impl CuckooFilter {
+    pub fn new(n_buckets: usize, bucket_size: usize, fp_bits: usize) -> Self {
+        Self::with_seed(n_buckets, bucket_size, fp_bits, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed(n_buckets: usize, bucket_size: usize, fp_bits: usize, seed: u64) -> Self {
+        // n_buckets is rounded up to a power of two. Evictions are drawn
+        // from the seed too, so the same seed and inserts give the same filter.
+        assert!(bucket_size > 0);
+        assert!(fp_bits > 1 && fp_bits <= 32);
+        let n_buckets = exp2(clog(max(n_buckets, 1)));
+
+        Self {
+            table: IntVec::new(fp_bits, n_buckets * bucket_size),
+            n_buckets,
+            b: bucket_size,
+            n_items: 0,
+            seeds: seeds_from(seed, 4),
+            seed,
+            hasher: SeaHash,
+        }
+    }
+
+    pub fn with_fpr(fpr: f32, n: usize) -> Self {
+        /* Create CF with fp rate `fpr` and `n` expected elements, 4 slot buckets.
+           A query compares 2b fingerprints, so fpr ~ 2b / 2^f. */
+        let b = 4;
+        let f = (2.0 * b as f32 / fpr).log2().ceil() as usize;
+        let n_buckets = (n as f32 / (b as f32 * LOAD)).ceil() as usize;
+        Self::new(n_buckets, b, max(f, 2))
+    }
+}
+
+impl<F: FilterHasher> CuckooFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> CuckooFilter<G> {
+        // Switch the hash function; only valid before inserting
+        CuckooFilter {
+            table: self.table,
+            n_buckets: self.n_buckets,
+            b: self.b,
+            n_items: self.n_items,
+            seeds: self.seeds,
+            seed: self.seed,
+            hasher,
+        }
+    }
+
+    pub fn try_insert<H: Hash>(&mut self, item: &H) -> Result<(), FilterError> {
+        // Insert item, evicting fingerprints to their alternate buckets if
+        // both buckets are full. If no place is found after MAX_KICKS
+        // evictions, every eviction is undone and Err(Full) is returned.
+        let (fp, i1, i2) = self.locate(item);
+        if self.put(i1, fp) || self.put(i2, fp) {
+            self.n_items += 1;
+            return Ok(())
+        }
+
+        // Eviction choices come from a SplitMix64 stream keyed by seed and item
+        let mut r = mix64(self.seed ^ ((fp as u64) << 32 | i1 as u64));
+        let mut kicked: Vec<(usize, u32)> = vec![]; // (slot, fingerprint it held)
+        let mut fp = fp;
+        let mut i = if r & 1 == 0 { i1 } else { i2 };
+        for _ in 0..MAX_KICKS {
+            r = mix64(r);
+            let slot = i * self.b + reduce(r, self.b);
+            let victim = self.table.get_int(slot);
+            self.table.set_int(slot, fp);
+            kicked.push((slot, victim));
+            fp = victim;
+            i = self.alt_bucket(i, fp);
+            if self.put(i, fp) {
+                self.n_items += 1;
+                return Ok(())
+            }
+        }
+
+        for &(slot, old) in kicked.iter().rev() {
+            self.table.set_int(slot, old);
+        }
+        Err(FilterError::Full)
+    }
+
+    pub fn remove<H: Hash>(&mut self, item: &H) -> bool {
+        // Remove one copy of item. Removing an item that was never inserted
+        // may remove another item sharing its fingerprint.
+        let (fp, i1, i2) = self.locate(item);
+        match self.find(i1, fp).or_else(|| self.find(i2, fp)) {
+            Some(slot) => {
+                self.table.set_int(slot, 0);
+                self.n_items -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn locate<H: Hash>(&self, item: &H) -> (u32, usize, usize) {
+        // Fingerprint and both candidate buckets of item
+        let h = seeded_hash(&self.hasher, &self.seeds, 0, item);
+        let i1 = (h as usize) & (self.n_buckets - 1);
+        let fp = ((h >> 32) % self.max_fp() as u64) as u32 + 1;
+        (fp, i1, self.alt_bucket(i1, fp))
+    }
+
+    fn alt_bucket(&self, i: usize, fp: u32) -> usize {
+        let h = seahash::hash(&fp.to_le_bytes());
+        (i ^ h as usize) & (self.n_buckets - 1)
+    }
+
+    fn max_fp(&self) -> u32 {
+        ((1_u64 << self.fp_bits()) - 1) as u32
+    }
+
+    fn find(&self, i: usize, fp: u32) -> Option<usize> {
+        (i * self.b..(i + 1) * self.b).find(|&s| self.table.get_int(s) == fp)
+    }
+
+    fn put(&mut self, i: usize, fp: u32) -> bool {
+        // Store fp in a free slot of bucket i
+        match self.find(i, 0) {
+            Some(slot) => {
+                self.table.set_int(slot, fp);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        // Number of stored items
+        self.n_items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_items == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.n_buckets * self.b
+    }
+
+    pub fn load_factor(&self) -> f32 {
+        self.n_items as f32 / self.capacity() as f32
+    }
+
+    pub fn n_buckets(&self) -> usize {
+        self.n_buckets
+    }
+
+    pub fn bucket_size(&self) -> usize {
+        self.b
+    }
+
+    pub fn fp_bits(&self) -> usize {
+        self.table.w_size()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for CuckooFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("CuckooFilter", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", self.table.space().inline().bytes),
+            SpaceBreakdown::leaf("seeds", std::mem::size_of_val::<[u64]>(&self.seeds)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod cuckoo_filter_tests {
+    use crate::cuckoo_filter::*;
+    use crate::hashing::CanonicalKmer;
+
+    #[test]
+    fn insert_query_remove() {
+        let mut cf = CuckooFilter::with_fpr(0.01, 1000);
+        assert_eq!(cf.bucket_size(), 4);
+        assert_eq!(cf.fp_bits(), 10);
+        for i in 0..1000 {
+            cf.insert(&i);
+        }
+        assert_eq!(cf.len(), 1000);
+        for i in 0..1000 {
+            assert!(cf.query(&i));
+        }
+        let fp = (1000..11000).filter(|i| cf.query(i)).count();
+        assert!(fp < 200, "{} false positives", fp);
+
+        for i in 0..500 {
+            assert!(cf.remove(&i));
+        }
+        assert_eq!(cf.len(), 500);
+        for i in 500..1000 {
+            assert!(cf.query(&i));
+        }
+        let fp = (0..500).filter(|i| cf.query(i)).count();
+        assert!(fp < 50);
+    }
+
+    #[test]
+    fn full() {
+        let mut cf = CuckooFilter::new(4, 2, 16);
+        let mut inserted = vec![];
+        let mut i = 0;
+        loop {
+            match cf.try_insert(&i) {
+                Ok(()) => inserted.push(i),
+                Err(e) => {
+                    assert_eq!(e, FilterError::Full);
+                    break
+                }
+            }
+            i += 1;
+        }
+        assert!(cf.len() <= cf.capacity());
+        assert_eq!(cf.len(), inserted.len());
+        // A failed insert leaves the filter unchanged
+        for x in inserted.iter() {
+            assert!(cf.query(x));
+        }
+    }
+
+    #[test]
+    fn seeded() {
+        let mut a = CuckooFilter::with_seed(8, 2, 12, 3);
+        let mut b = CuckooFilter::with_seed(8, 2, 12, 3);
+        // Past the load where evictions start
+        for i in 0..14 {
+            assert_eq!(a.try_insert(&i).is_ok(), b.try_insert(&i).is_ok());
+        }
+        assert_eq!(a.seed(), 3);
+        assert_eq!(bincode::serialize(&a).unwrap(), bincode::serialize(&b).unwrap());
+    }
+
+    #[test]
+    fn hasher() {
+        let mut cf = CuckooFilter::with_seed(64, 4, 12, 1).with_hasher(CanonicalKmer);
+        cf.insert(&"GATTACA");
+        assert!(cf.query(&"TGTAATC"));
+        assert!(cf.remove(&"TGTAATC"));
+        assert!(cf.is_empty());
+        let encoded = bincode::serialize(&cf).unwrap();
+        assert!(bincode::deserialize::<CuckooFilter>(&encoded).is_err());
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut cf = CuckooFilter::new(16, 4, 12);
+        cf.insert(&"x");
+        cf.insert(&"x");
+        assert!(cf.remove(&"x"));
+        assert!(cf.query(&"x"));
+        assert!(cf.remove(&"x"));
+        assert!(!cf.query(&"x"));
+        assert!(!cf.remove(&"x"));
+    }
+}
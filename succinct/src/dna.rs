@@ -0,0 +1,404 @@
+use super::bv::IntVec;
+use super::math::{cdiv, clog};
+use serde::{Serialize, Deserialize};
+use std::cmp::{max, min};
+
+// Bases per counting block
+const BLOCK: usize = 256;
+const BASES: [char; 4] = ['A', 'C', 'G', 'T'];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnaSeq {
+    // Nucleotide sequence with A/C/G/T packed in 2 bits per base. Any other
+    // char (N, IUPAC codes) is an exception: it is stored as code 0 in
+    // `codes` and recorded in runs of equal chars. `counts[b][k]` holds the
+    // occurrences of base b before block k, not counting exceptions, so
+    // rank is a table lookup plus popcounts over one block.
+    //
+    // Soft-masked (lowercase) a/c/g/t are case-folded into `codes` and the
+    // masked intervals kept as runs, so access returns the original case
+    // while rank and select treat 'a' and 'A' as the same base.
+    n: usize,
+    codes: IntVec,
+    counts: Vec<IntVec>,
+    run_start: IntVec,
+    run_len: IntVec,
+    run_char: IntVec,
+    run_cum: IntVec, // exception bases before each run
+    exc_chars: Vec<u8>, // distinct exception chars, ascending
+    exc_runs: Vec<IntVec>, // per exception char, its runs in order
+    exc_cum: Vec<IntVec>, // per exception char, its bases before each of its runs
+    mask_start: IntVec,
+    mask_len: IntVec,
+}
+
+impl DnaSeq {
+    pub fn new(s: &str) -> Self {
+        assert!(s.is_ascii());
+        let bytes = s.as_bytes();
+        let n = bytes.len();
+        let mut codes = IntVec::new(2, n);
+
+        let mut runs: Vec<(u32, u32, u32)> = vec![];
+        let mut masks: Vec<(u32, u32)> = vec![];
+        for (i, &c) in bytes.iter().enumerate() {
+            match Self::code(c as char) {
+                Some(x) => {
+                    codes.set_int(i, x);
+                    if c.is_ascii_lowercase() {
+                        match masks.last_mut() {
+                            Some(m) if (m.0 + m.1) as usize == i => m.1 += 1,
+                            _ => masks.push((i as u32, 1)),
+                        }
+                    }
+                }
+                None => match runs.last_mut() {
+                    Some(r) if (r.0 + r.1) as usize == i && r.2 == c as u32 => r.1 += 1,
+                    _ => runs.push((i as u32, 1, c as u32)),
+                },
+            }
+        }
+
+        let n_blocks = cdiv(n, BLOCK) + 1;
+        let w = max(clog(n + 1), 1);
+        let mut counts = vec![IntVec::new(w, n_blocks); 4];
+        let mut acc = [0_u32; 4];
+        for (i, &c) in bytes.iter().enumerate() {
+            if let Some(x) = Self::code(c as char) {
+                acc[x as usize] += 1;
+            }
+            if (i + 1).is_multiple_of(BLOCK) || i + 1 == n {
+                for b in 0..4 {
+                    counts[b].set_int(cdiv(i + 1, BLOCK), acc[b]);
+                }
+            }
+        }
+
+        let k = runs.len();
+        let mut run_start = IntVec::new(w, k);
+        let mut run_len = IntVec::new(w, k);
+        let mut run_char = IntVec::new(8, k);
+        let mut run_cum = IntVec::new(w, k);
+        let mut cum = 0;
+        for (j, &(start, len, c)) in runs.iter().enumerate() {
+            run_start.set_int(j, start);
+            run_len.set_int(j, len);
+            run_char.set_int(j, c);
+            run_cum.set_int(j, cum);
+            cum += len;
+        }
+
+        let mut exc_chars: Vec<u8> = runs.iter().map(|r| r.2 as u8).collect();
+        exc_chars.sort_unstable();
+        exc_chars.dedup();
+        let mut exc_runs = vec![];
+        let mut exc_cum = vec![];
+        for &c in exc_chars.iter() {
+            let own: Vec<usize> = (0..k).filter(|&j| runs[j].2 == c as u32).collect();
+            let mut js = IntVec::new(max(clog(k + 1), 1), own.len());
+            let mut cs = IntVec::new(w, own.len());
+            let mut cum = 0;
+            for (t, &j) in own.iter().enumerate() {
+                js.set_int(t, j as u32);
+                cs.set_int(t, cum);
+                cum += runs[j].1;
+            }
+            exc_runs.push(js);
+            exc_cum.push(cs);
+        }
+
+        let mut mask_start = IntVec::new(w, masks.len());
+        let mut mask_len = IntVec::new(w, masks.len());
+        for (j, &(start, len)) in masks.iter().enumerate() {
+            mask_start.set_int(j, start);
+            mask_len.set_int(j, len);
+        }
+
+        Self {
+            n,
+            codes,
+            counts,
+            run_start,
+            run_len,
+            run_char,
+            run_cum,
+            exc_chars,
+            exc_runs,
+            exc_cum,
+            mask_start,
+            mask_len,
+        }
+    }
+
+    fn code(c: char) -> Option<u32> {
+        // 2-bit code of a base, case-insensitive
+        let c = c.to_ascii_uppercase();
+        BASES.iter().position(|&b| b == c).map(|x| x as u32)
+    }
+
+    fn last_le(v: &IntVec, i: usize) -> Option<usize> {
+        // Last index of ascending v with value at most i
+        let (mut lo, mut hi) = (0, v.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if v.get_int(mid) as usize <= i { lo = mid + 1 } else { hi = mid }
+        }
+        if lo == 0 { None } else { Some(lo - 1) }
+    }
+
+    fn run_at(&self, i: usize) -> Option<usize> {
+        // Last run starting at or before i
+        Self::last_le(&self.run_start, i)
+    }
+
+    fn is_masked(&self, i: usize) -> bool {
+        match Self::last_le(&self.mask_start, i) {
+            Some(j) => i < (self.mask_start.get_int(j) + self.mask_len.get_int(j)) as usize,
+            None => false,
+        }
+    }
+
+    fn exc_index(&self, c: char) -> Option<usize> {
+        if !c.is_ascii() { return None }
+        self.exc_chars.binary_search(&(c as u8)).ok()
+    }
+
+    fn is_exception(&self, i: usize) -> bool {
+        match self.run_at(i) {
+            Some(j) => i < (self.run_start.get_int(j) + self.run_len.get_int(j)) as usize,
+            None => false,
+        }
+    }
+
+    fn exceptions_before(&self, i: usize) -> usize {
+        // Exception bases in [0, i)
+        if i == 0 { return 0 }
+        match self.run_at(i - 1) {
+            Some(j) => {
+                let start = self.run_start.get_int(j) as usize;
+                let len = self.run_len.get_int(j) as usize;
+                self.run_cum.get_int(j) as usize + min(len, i - start)
+            }
+            None => 0,
+        }
+    }
+
+    fn count_code(&self, x: u32, lo: usize, hi: usize) -> usize {
+        // Occurrences of code x in codes[lo..hi), 16 codes per word
+        let pattern = x * 0x5555_5555;
+        let bits = self.codes.bits();
+        let mut count = 0;
+        let mut p = lo;
+        while p < hi {
+            let k = min(16, hi - p);
+            let w = 2 * k;
+            let diff = bits.get_int(2 * p, w) ^ (pattern >> (32 - w));
+            let low = 0x5555_5555_u32 >> (32 - w);
+            count += (!(diff | (diff >> 1)) & low).count_ones() as usize;
+            p += k;
+        }
+        count
+    }
+
+    fn rank_before(&self, c: char, i: usize) -> usize {
+        // Occurrences of c in [0, i)
+        match Self::code(c) {
+            Some(x) => {
+                let k = i / BLOCK;
+                let mut r = self.counts[x as usize].get_int(k) as usize;
+                r += self.count_code(x, k * BLOCK, i);
+                if x == 0 {
+                    r -= self.exceptions_before(i) - self.exceptions_before(k * BLOCK);
+                }
+                r
+            }
+            None => {
+                // Last run of c starting before i, plus the bases of c before it
+                let e = match self.exc_index(c) { Some(e) => e, None => return 0 };
+                if i == 0 { return 0 }
+                let last = match self.run_at(i - 1) { Some(j) => j, None => return 0 };
+                let t = match Self::last_le(&self.exc_runs[e], last) { Some(t) => t, None => return 0 };
+                let j = self.exc_runs[e].get_int(t) as usize;
+                let start = self.run_start.get_int(j) as usize;
+                self.exc_cum[e].get_int(t) as usize + min(self.run_len.get_int(j) as usize, i - start)
+            }
+        }
+    }
+
+    pub fn access(&self, i: usize) -> char {
+        assert!(i < self.n);
+        if let Some(j) = self.run_at(i) {
+            if i < (self.run_start.get_int(j) + self.run_len.get_int(j)) as usize {
+                return self.run_char.get_int(j) as u8 as char;
+            }
+        }
+        let c = BASES[self.codes.get_int(i) as usize];
+        if self.is_masked(i) { c.to_ascii_lowercase() } else { c }
+    }
+
+    pub fn rank(&self, c: char, i: usize) -> usize {
+        // Occurrences of c in [0, i]
+        assert!(i < self.n);
+        self.rank_before(c, i + 1)
+    }
+
+    pub fn select(&self, c: char, rank: usize) -> Option<usize> {
+        // Position of the rank-th (1-based) c
+        if rank == 0 { return None }
+        match Self::code(c) {
+            Some(x) => {
+                // Last block with fewer than rank occurrences before it
+                let counts = &self.counts[x as usize];
+                let (mut lo, mut hi) = (0, counts.len());
+                while lo + 1 < hi {
+                    let mid = (lo + hi) / 2;
+                    if (counts.get_int(mid) as usize) < rank { lo = mid } else { hi = mid }
+                }
+                if hi == counts.len() { return None }
+                let mut r = rank - counts.get_int(lo) as usize;
+                for p in lo * BLOCK..min((lo + 1) * BLOCK, self.n) {
+                    if self.codes.get_int(p) == x && !(x == 0 && self.is_exception(p)) {
+                        r -= 1;
+                        if r == 0 { return Some(p) }
+                    }
+                }
+                None
+            }
+            None => {
+                // Last run of c with fewer than rank bases of c before it
+                let e = self.exc_index(c)?;
+                let t = Self::last_le(&self.exc_cum[e], rank - 1)?;
+                let j = self.exc_runs[e].get_int(t) as usize;
+                let r = rank - self.exc_cum[e].get_int(t) as usize;
+                if r > self.run_len.get_int(j) as usize { return None }
+                Some(self.run_start.get_int(j) as usize + r - 1)
+            }
+        }
+    }
+
+    pub fn count(&self, c: char) -> usize {
+        self.rank_before(c, self.n)
+    }
+
+    pub fn n_exception_runs(&self) -> usize {
+        self.run_start.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.codes.size_of();
+        size += self.counts.iter().map(|c| c.size_of()).sum::<usize>();
+        size += self.run_start.size_of();
+        size += self.run_len.size_of();
+        size += self.run_char.size_of();
+        size += self.run_cum.size_of();
+        size += self.exc_chars.len();
+        size += self.exc_runs.iter().chain(self.exc_cum.iter()).map(|v| v.size_of()).sum::<usize>();
+        size += self.mask_start.size_of();
+        size += self.mask_len.size_of();
+        size
+    }
+}
+
+#[cfg(test)]
+mod dna_tests {
+    use crate::dna::*;
+    use crate::wt::WT;
+    use rand::Rng;
+
+    fn check(s: &str) {
+        let dna = DnaSeq::new(s);
+        let chars: Vec<char> = s.chars().collect();
+        let mut counts = [0; 128];
+        for (i, &c) in chars.iter().enumerate() {
+            assert_eq!(dna.access(i), c);
+            counts[c as usize] += 1;
+            for &b in &['A', 'C', 'G', 'T', 'N', 'R'] {
+                assert_eq!(dna.rank(b, i), counts[b as usize]);
+            }
+            assert_eq!(dna.select(c, counts[c as usize]), Some(i));
+        }
+        for &b in &['A', 'C', 'G', 'T', 'N', 'R'] {
+            assert_eq!(dna.count(b), counts[b as usize]);
+            assert_eq!(dna.select(b, counts[b as usize] + 1), None);
+        }
+    }
+
+    #[test]
+    fn small() {
+        check("ACGTNNNACGTRA");
+        check("NNNN");
+        check("A");
+        let dna = DnaSeq::new("GATTACANNNNNNGATTACA");
+        assert_eq!(dna.n_exception_runs(), 1);
+        assert_eq!(dna.rank('A', 12), 3);
+        assert_eq!(dna.select('A', 4), Some(14));
+    }
+
+    #[test]
+    fn soft_masked() {
+        // Lowercase bases keep their case on access but rank and select
+        // as their uppercase base, without becoming exceptions
+        let s = "ACgtacGTNNnnACGT";
+        let dna = DnaSeq::new(s);
+        assert_eq!(dna.n_exception_runs(), 2);
+        let mut counts = [0; 128];
+        for (i, c) in s.chars().enumerate() {
+            assert_eq!(dna.access(i), c);
+            let folded = if "acgt".contains(c) { c.to_ascii_uppercase() } else { c };
+            counts[folded as usize] += 1;
+            for &b in &['A', 'C', 'G', 'T'] {
+                assert_eq!(dna.rank(b, i), counts[b as usize]);
+                assert_eq!(dna.rank(b.to_ascii_lowercase(), i), counts[b as usize]);
+            }
+            assert_eq!(dna.rank('N', i), counts['N' as usize]);
+            assert_eq!(dna.rank('n', i), counts['n' as usize]);
+        }
+        assert_eq!(dna.select('a', 2), Some(4));
+        assert_eq!(dna.select('G', 2), Some(6));
+        assert_eq!(dna.select('n', 2), Some(11));
+        assert_eq!(dna.count('t'), 3);
+
+        let masked = "acgt".repeat(500);
+        let dna = DnaSeq::new(&masked);
+        assert_eq!(dna.n_exception_runs(), 0);
+        assert!(dna.size_of() < masked.len());
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = rand::thread_rng();
+        let alphabet = ['A', 'C', 'G', 'T', 'A', 'C', 'G', 'T', 'N', 'R'];
+        let mut s = String::new();
+        while s.len() < 3000 {
+            let c = alphabet[rng.gen_range(0, alphabet.len())];
+            let run = if c == 'N' { rng.gen_range(1, 40) } else { 1 };
+            for _ in 0..run {
+                s.push(c);
+            }
+        }
+        check(&s);
+    }
+
+    #[test]
+    fn matches_wt() {
+        let s = "ACGTTGCANNACGGTACCAGT".repeat(50);
+        let dna = DnaSeq::new(&s);
+        let wt = WT::new(&s);
+        for i in 0..s.len() {
+            assert_eq!(dna.access(i), wt.access(i));
+            for &b in &['A', 'C', 'G', 'T', 'N'] {
+                assert_eq!(dna.rank(b, i), wt.rank(b, i));
+            }
+        }
+    }
+}
@@ -0,0 +1,601 @@
+use serde::{Serialize, Deserialize};
+use std::cmp::max;
+use std::mem;
+
+// Leaves hold up to LEAF_BITS bits and are split in half when they overflow.
+// A leaf below MIN_LEAF_BITS is merged with its neighbour, or refilled from
+// it when both do not fit in one leaf.
+const LEAF_BITS: usize = 1024;
+const MIN_LEAF_BITS: usize = LEAF_BITS / 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynBitVec {
+    // Updatable bit vector: an AVL tree whose leaves are small packed bit
+    // vectors and whose inner nodes store the length and number of ones of
+    // their subtree. get, set, insert, remove, rank and select are
+    // O(log n) (plus O(LEAF_BITS / 64) word operations at the leaf).
+    root: Node,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Node {
+    Leaf(Leaf),
+    Inner(Box<Inner>),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Leaf {
+    bits: Vec<u64>, // bit i is bit i % 64 of word i / 64
+    len: usize,
+    ones: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Inner {
+    left: Node,
+    right: Node,
+    len: usize,
+    ones: usize,
+    height: usize,
+}
+
+impl Default for DynBitVec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DynBitVec {
+    pub fn new() -> Self {
+        Self { root: Node::Leaf(Leaf::default()) }
+    }
+
+    pub fn from_bits(bits: &[bool]) -> Self {
+        let mut bv = Self::new();
+        for &b in bits.iter() {
+            bv.push(b);
+        }
+        bv
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.root.ones()
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len());
+        let mut node = &self.root;
+        let mut i = i;
+        loop {
+            match node {
+                Node::Leaf(l) => return l.get(i),
+                Node::Inner(n) => {
+                    if i < n.left.len() {
+                        node = &n.left;
+                    } else {
+                        i -= n.left.len();
+                        node = &n.right;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn set(&mut self, i: usize, b: bool) {
+        assert!(i < self.len());
+        Self::set_(&mut self.root, i, b);
+    }
+
+    fn set_(node: &mut Node, i: usize, b: bool) {
+        match node {
+            Node::Leaf(l) => l.set(i, b),
+            Node::Inner(n) => {
+                if i < n.left.len() {
+                    Self::set_(&mut n.left, i, b);
+                } else {
+                    Self::set_(&mut n.right, i - n.left.len(), b);
+                }
+                n.update();
+            }
+        }
+    }
+
+    pub fn push(&mut self, b: bool) {
+        self.insert(self.len(), b);
+    }
+
+    pub fn insert(&mut self, i: usize, b: bool) {
+        // Insert b before position i
+        assert!(i <= self.len());
+        Self::insert_(&mut self.root, i, b);
+    }
+
+    fn insert_(node: &mut Node, i: usize, b: bool) {
+        match node {
+            Node::Leaf(l) => {
+                l.insert(i, b);
+                if l.len > LEAF_BITS {
+                    let right = l.split_off(l.len / 2);
+                    let left = mem::take(l);
+                    *node = Node::Inner(Box::new(Inner::new(Node::Leaf(left), Node::Leaf(right))));
+                }
+            }
+            Node::Inner(n) => {
+                if i <= n.left.len() {
+                    Self::insert_(&mut n.left, i, b);
+                } else {
+                    Self::insert_(&mut n.right, i - n.left.len(), b);
+                }
+                n.update();
+                Self::rebalance(node);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, i: usize) -> bool {
+        // Remove and return the bit at position i
+        assert!(i < self.len());
+        Self::remove_(&mut self.root, i)
+    }
+
+    fn remove_(node: &mut Node, i: usize) -> bool {
+        match node {
+            Node::Leaf(l) => l.remove(i),
+            Node::Inner(n) => {
+                let from_left = i < n.left.len();
+                let b = if from_left {
+                    Self::remove_(&mut n.left, i)
+                } else {
+                    Self::remove_(&mut n.right, i - n.left.len())
+                };
+
+                // Fix an underflowing leaf against the adjacent leaf, which
+                // is the first leaf of the right subtree or the last of the left
+                let (child, other) = if from_left { (&mut n.left, &mut n.right) } else { (&mut n.right, &mut n.left) };
+                let short = match child {
+                    Node::Leaf(l) if l.len < MIN_LEAF_BITS => Some(mem::take(l)),
+                    _ => None,
+                };
+                match short {
+                    Some(l) if l.len + other.edge_leaf_len(from_left) <= LEAF_BITS => {
+                        // Merge into the neighbour and drop the leaf
+                        other.with_edge_leaf(from_left, |nb| {
+                            if from_left {
+                                let mut merged = l;
+                                merged.append(nb);
+                                *nb = merged;
+                            } else {
+                                nb.append(&l);
+                            }
+                        });
+                        let other = mem::replace(other, Node::empty());
+                        *node = other;
+                    }
+                    Some(mut l) => {
+                        // Borrow bits from the neighbour to even the two out
+                        other.with_edge_leaf(from_left, |nb| {
+                            let k = (nb.len - l.len) / 2;
+                            if from_left {
+                                let rest = nb.split_off(k);
+                                l.append(nb);
+                                *nb = rest;
+                            } else {
+                                let mut tail = nb.split_off(nb.len - k);
+                                tail.append(&l);
+                                l = tail;
+                            }
+                        });
+                        *child = Node::Leaf(l);
+                        n.update();
+                    }
+                    None => {
+                        n.update();
+                        Self::rebalance(node);
+                    }
+                }
+                b
+            }
+        }
+    }
+
+    pub fn n_leaves(&self) -> usize {
+        self.root.n_leaves()
+    }
+
+    pub fn rank1(&self, i: usize) -> usize {
+        // Number of ones in [0, i]
+        assert!(i < self.len());
+        let mut node = &self.root;
+        let mut i = i;
+        let mut r = 0;
+        loop {
+            match node {
+                Node::Leaf(l) => return r + l.rank1(i),
+                Node::Inner(n) => {
+                    if i < n.left.len() {
+                        node = &n.left;
+                    } else {
+                        i -= n.left.len();
+                        r += n.left.ones();
+                        node = &n.right;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn rank0(&self, i: usize) -> usize {
+        i + 1 - self.rank1(i)
+    }
+
+    pub fn rank(&self, b: bool, i: usize) -> usize {
+        if b { self.rank1(i) } else { self.rank0(i) }
+    }
+
+    pub fn select(&self, b: bool, r: usize) -> Option<usize> {
+        // Position of the r-th (1-based) b
+        let total = if b { self.count_ones() } else { self.len() - self.count_ones() };
+        if r == 0 || r > total { return None }
+        let mut node = &self.root;
+        let mut r = r;
+        let mut offset = 0;
+        loop {
+            match node {
+                Node::Leaf(l) => return Some(offset + l.select(b, r)),
+                Node::Inner(n) => {
+                    let left = if b { n.left.ones() } else { n.left.len() - n.left.ones() };
+                    if r <= left {
+                        node = &n.left;
+                    } else {
+                        r -= left;
+                        offset += n.left.len();
+                        node = &n.right;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn select1(&self, r: usize) -> Option<usize> {
+        self.select(true, r)
+    }
+
+    pub fn select0(&self, r: usize) -> Option<usize> {
+        self.select(false, r)
+    }
+
+    pub fn to_vec(&self) -> Vec<bool> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size in bytes
+        std::mem::size_of::<Self>() + self.root.size_of()
+    }
+
+    fn rebalance(node: &mut Node) {
+        let n = match node {
+            Node::Inner(n) => n,
+            Node::Leaf(_) => return,
+        };
+        let balance = n.left.height() as isize - n.right.height() as isize;
+        if balance > 1 {
+            if n.left.balance() < 0 {
+                Self::rotate_left(&mut n.left);
+            }
+            Self::rotate_right(node);
+        } else if balance < -1 {
+            if n.right.balance() > 0 {
+                Self::rotate_right(&mut n.right);
+            }
+            Self::rotate_left(node);
+        }
+    }
+
+    fn rotate_right(node: &mut Node) {
+        // (x, y), z -> x, (y, z)
+        let mut n = node.take_inner();
+        let mut l = n.left.take_inner();
+        n.left = mem::replace(&mut l.right, Node::empty());
+        n.update();
+        l.right = Node::Inner(n);
+        l.update();
+        *node = Node::Inner(l);
+    }
+
+    fn rotate_left(node: &mut Node) {
+        // x, (y, z) -> (x, y), z
+        let mut n = node.take_inner();
+        let mut r = n.right.take_inner();
+        n.right = mem::replace(&mut r.left, Node::empty());
+        n.update();
+        r.left = Node::Inner(n);
+        r.update();
+        *node = Node::Inner(r);
+    }
+}
+
+impl Node {
+    fn empty() -> Self {
+        Node::Leaf(Leaf::default())
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(l) => l.len,
+            Node::Inner(n) => n.len,
+        }
+    }
+
+    fn ones(&self) -> usize {
+        match self {
+            Node::Leaf(l) => l.ones,
+            Node::Inner(n) => n.ones,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Inner(n) => n.height,
+        }
+    }
+
+    fn balance(&self) -> isize {
+        match self {
+            Node::Leaf(_) => 0,
+            Node::Inner(n) => n.left.height() as isize - n.right.height() as isize,
+        }
+    }
+
+    fn n_leaves(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Inner(n) => n.left.n_leaves() + n.right.n_leaves(),
+        }
+    }
+
+    fn edge_leaf_len(&self, first: bool) -> usize {
+        // Length of the first (or last) leaf of the subtree
+        match self {
+            Node::Leaf(l) => l.len,
+            Node::Inner(n) => if first { n.left.edge_leaf_len(first) } else { n.right.edge_leaf_len(first) },
+        }
+    }
+
+    fn with_edge_leaf<F: FnOnce(&mut Leaf)>(&mut self, first: bool, f: F) {
+        // Apply f to the first (or last) leaf and update counts on the path
+        match self {
+            Node::Leaf(l) => f(l),
+            Node::Inner(n) => {
+                if first { n.left.with_edge_leaf(first, f) } else { n.right.with_edge_leaf(first, f) }
+                n.update();
+            }
+        }
+    }
+
+    fn take_inner(&mut self) -> Box<Inner> {
+        match mem::replace(self, Node::empty()) {
+            Node::Inner(n) => n,
+            Node::Leaf(_) => unreachable!(),
+        }
+    }
+
+    fn size_of(&self) -> usize {
+        match self {
+            Node::Leaf(l) => std::mem::size_of_val::<[u64]>(&*l.bits),
+            Node::Inner(n) => std::mem::size_of::<Inner>() + n.left.size_of() + n.right.size_of(),
+        }
+    }
+}
+
+impl Inner {
+    fn new(left: Node, right: Node) -> Self {
+        let mut n = Self { left, right, len: 0, ones: 0, height: 0 };
+        n.update();
+        n
+    }
+
+    fn update(&mut self) {
+        self.len = self.left.len() + self.right.len();
+        self.ones = self.left.ones() + self.right.ones();
+        self.height = 1 + max(self.left.height(), self.right.height());
+    }
+}
+
+impl Leaf {
+    fn get(&self, i: usize) -> bool {
+        (self.bits[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    fn set(&mut self, i: usize, b: bool) {
+        let old = self.get(i);
+        if b {
+            self.bits[i / 64] |= 1 << (i % 64);
+        } else {
+            self.bits[i / 64] &= !(1 << (i % 64));
+        }
+        self.ones = self.ones + b as usize - old as usize;
+    }
+
+    fn insert(&mut self, i: usize, b: bool) {
+        if self.len == self.bits.len() * 64 {
+            self.bits.push(0);
+        }
+        let w = i / 64;
+        let off = i % 64;
+
+        // Shift the words after w up by one bit
+        let mut carry = self.bits[w] >> 63;
+        for k in w + 1..self.bits.len() {
+            let next = self.bits[k] >> 63;
+            self.bits[k] = (self.bits[k] << 1) | carry;
+            carry = next;
+        }
+
+        let low = (1_u64 << off) - 1;
+        let word = self.bits[w];
+        self.bits[w] = (word & low) | ((word & !low) << 1) | ((b as u64) << off);
+        self.len += 1;
+        self.ones += b as usize;
+    }
+
+    fn remove(&mut self, i: usize) -> bool {
+        let b = self.get(i);
+        let w = i / 64;
+        let off = i % 64;
+
+        let low = (1_u64 << off) - 1;
+        let word = self.bits[w];
+        self.bits[w] = (word & low) | ((word >> 1) & !low);
+
+        // Shift the words after w down by one bit
+        for k in w + 1..self.bits.len() {
+            self.bits[k - 1] |= (self.bits[k] & 1) << 63;
+            self.bits[k] >>= 1;
+        }
+
+        self.len -= 1;
+        self.ones -= b as usize;
+        if self.bits.len() * 64 >= self.len + 64 {
+            self.bits.pop();
+        }
+        b
+    }
+
+    fn split_off(&mut self, at: usize) -> Leaf {
+        // Move bits [at, len) into a new leaf
+        let mut right = Leaf::default();
+        for i in at..self.len {
+            right.insert(right.len, self.get(i));
+        }
+        while self.len > at {
+            self.remove(self.len - 1);
+        }
+        right
+    }
+
+    fn append(&mut self, other: &Leaf) {
+        for i in 0..other.len {
+            self.insert(self.len, other.get(i));
+        }
+    }
+
+    fn rank1(&self, i: usize) -> usize {
+        // Number of ones in [0, i]
+        let w = i / 64;
+        let off = i % 64;
+        let mut r: usize = self.bits[..w].iter().map(|x| x.count_ones() as usize).sum();
+        let mask = if off == 63 { !0 } else { (1_u64 << (off + 1)) - 1 };
+        r += (self.bits[w] & mask).count_ones() as usize;
+        r
+    }
+
+    fn select(&self, b: bool, r: usize) -> usize {
+        // Position of the r-th b; it must exist in this leaf
+        let mut r = r;
+        for (k, &word) in self.bits.iter().enumerate() {
+            let mut x = if b { word } else { !word };
+            let c = x.count_ones() as usize;
+            if r > c {
+                r -= c;
+                continue;
+            }
+            for _ in 1..r {
+                x &= x - 1; // clear lowest set bit
+            }
+            return k * 64 + x.trailing_zeros() as usize;
+        }
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod dyn_bv_tests {
+    use crate::dyn_bv::*;
+    use rand::Rng;
+
+    fn check(bv: &DynBitVec, model: &[bool]) {
+        assert_eq!(bv.len(), model.len());
+        assert_eq!(bv.to_vec(), model);
+        let mut ones = 0;
+        for (i, &b) in model.iter().enumerate() {
+            ones += b as usize;
+            assert_eq!(bv.rank1(i), ones);
+            assert_eq!(bv.select(b, bv.rank(b, i)), Some(i));
+        }
+        assert_eq!(bv.select1(ones + 1), None);
+        assert_eq!(bv.select0(0), None);
+    }
+
+    #[test]
+    fn push_get() {
+        let bits: Vec<bool> = (0..3000).map(|i| i % 3 == 0).collect();
+        let bv = DynBitVec::from_bits(&bits);
+        check(&bv, &bits);
+    }
+
+    #[test]
+    fn insert_remove() {
+        let mut rng = rand::thread_rng();
+        let mut bv = DynBitVec::new();
+        let mut model = vec![];
+        for _ in 0..5000 {
+            let i = rng.gen_range(0, model.len() + 1);
+            let b = rng.gen::<bool>();
+            bv.insert(i, b);
+            model.insert(i, b);
+        }
+        check(&bv, &model);
+
+        for _ in 0..4000 {
+            let i = rng.gen_range(0, model.len());
+            assert_eq!(bv.remove(i), model.remove(i));
+            if rng.gen_range(0, 4) == 0 {
+                let i = rng.gen_range(0, model.len());
+                let b = rng.gen::<bool>();
+                bv.set(i, b);
+                model[i] = b;
+            }
+        }
+        check(&bv, &model);
+
+        while let Some(b) = model.pop() {
+            assert_eq!(bv.remove(model.len()), b);
+        }
+        assert!(bv.is_empty());
+    }
+
+    #[test]
+    fn delete_heavy() {
+        // Underflowing leaves are merged or refilled, so the leaf count
+        // follows the length down
+        let mut rng = rand::thread_rng();
+        let mut model: Vec<bool> = (0..40_000).map(|_| rng.gen::<bool>()).collect();
+        let mut bv = DynBitVec::from_bits(&model);
+        let max_leaves = |len: usize| std::cmp::max(1, len / MIN_LEAF_BITS);
+        assert!(bv.n_leaves() <= max_leaves(bv.len()));
+
+        while model.len() > 2000 {
+            let i = rng.gen_range(0, model.len());
+            assert_eq!(bv.remove(i), model.remove(i));
+            assert!(bv.n_leaves() <= max_leaves(bv.len()));
+        }
+        check(&bv, &model);
+
+        // Deleting a prefix only touches the leftmost leaves
+        for _ in 0..1900 {
+            assert_eq!(bv.remove(0), model.remove(0));
+        }
+        assert_eq!(bv.n_leaves(), 1);
+        check(&bv, &model);
+    }
+}
@@ -0,0 +1,254 @@
+use super::dyn_bv::DynBitVec;
+use super::wt::CharTable;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynWT {
+    // Levelwise wavelet tree over updatable bit vectors. The alphabet is
+    // fixed at construction; the text can grow and shrink. All operations
+    // walk the levels once, so they take O(log n log sigma).
+    n: usize,
+    bv: Vec<DynBitVec>,
+    char_table: CharTable,
+}
+
+impl DynWT {
+    pub fn new(alphabet: &str) -> Self {
+        // Empty text over the chars in alphabet
+        assert!(!alphabet.is_empty());
+        let char_table = CharTable::new(alphabet);
+        Self {
+            n: 0,
+            bv: vec![DynBitVec::new(); char_table.width()],
+            char_table,
+        }
+    }
+
+    pub fn from_text(s: &str) -> Self {
+        // Text s over its own alphabet
+        let mut wt = Self::new(s);
+        for c in s.chars() {
+            wt.push(c);
+        }
+        wt
+    }
+
+    fn count(bv: &DynBitVec, b: bool, i: usize) -> usize {
+        // Occurrences of b in [0, i)
+        if i == 0 { 0 } else { bv.rank(b, i - 1) }
+    }
+
+    pub fn insert(&mut self, i: usize, c: char) {
+        // Insert c before position i
+        assert!(i <= self.n);
+        assert!(self.char_table.in_charset(c));
+        let mut l = 0;
+        let mut r = self.n;
+        let mut pos = i;
+        for k in 0..self.bv.len() {
+            // Node boundaries are computed before the level changes
+            let b = self.char_table.get_bit(k, c);
+            let zl = Self::count(&self.bv[k], false, l);
+            let zeros_before = Self::count(&self.bv[k], false, l + pos) - zl;
+            let zeros = Self::count(&self.bv[k], false, r) - zl;
+            self.bv[k].insert(l + pos, b);
+            if b {
+                pos -= zeros_before;
+                l += zeros;
+            } else {
+                pos = zeros_before;
+                r = l + zeros;
+            }
+        }
+        self.n += 1;
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.insert(self.n, c);
+    }
+
+    pub fn remove(&mut self, i: usize) -> char {
+        // Remove and return the char at position i
+        assert!(i < self.n);
+        let mut l = 0;
+        let mut r = self.n;
+        let mut pos = i;
+        let mut char_i = 0;
+        for k in 0..self.bv.len() {
+            let zl = Self::count(&self.bv[k], false, l);
+            let zeros_before = Self::count(&self.bv[k], false, l + pos) - zl;
+            let zeros = Self::count(&self.bv[k], false, r) - zl;
+            let b = self.bv[k].remove(l + pos);
+            char_i = (char_i << 1) | b as usize;
+            if b {
+                pos -= zeros_before;
+                l += zeros;
+            } else {
+                pos = zeros_before;
+                r = l + zeros;
+            }
+        }
+        self.n -= 1;
+        self.char_table.get_char(char_i)
+    }
+
+    pub fn access(&self, i: usize) -> char {
+        assert!(i < self.n);
+        let mut l = 0;
+        let mut r = self.n;
+        let mut pos = i;
+        let mut char_i = 0;
+        for bv in self.bv.iter() {
+            let b = bv.get(l + pos);
+            char_i = (char_i << 1) | b as usize;
+            let bl = Self::count(bv, b, l);
+            pos = Self::count(bv, b, l + pos) - bl;
+            let zeros = Self::count(bv, false, r) - Self::count(bv, false, l);
+            if b { l += zeros } else { r = l + zeros }
+        }
+        self.char_table.get_char(char_i)
+    }
+
+    pub fn rank(&self, c: char, i: usize) -> usize {
+        // Occurrences of c in [0, i]
+        assert!(i < self.n);
+        let mut l = 0;
+        let mut r = self.n;
+        let mut cnt = i + 1;
+        for (k, bv) in self.bv.iter().enumerate() {
+            let b = self.char_table.get_bit(k, c);
+            let bl = Self::count(bv, b, l);
+            cnt = Self::count(bv, b, l + cnt) - bl;
+            if cnt == 0 { return 0 }
+            let zeros = Self::count(bv, false, r) - Self::count(bv, false, l);
+            if b { l += zeros } else { r = l + zeros }
+        }
+        cnt
+    }
+
+    pub fn select(&self, c: char, rank: usize) -> Option<usize> {
+        // Position of the rank-th (1-based) c
+        if rank == 0 { return None }
+        let mut l = 0;
+        let mut r = self.n;
+        let mut stack = vec![];
+        for (k, bv) in self.bv.iter().enumerate() {
+            let b = self.char_table.get_bit(k, c);
+            stack.push((l, b));
+            let zeros = Self::count(bv, false, r) - Self::count(bv, false, l);
+            if b { l += zeros } else { r = l + zeros }
+        }
+        if rank > r - l { return None }
+
+        // Walk back up, mapping the position into each parent
+        let mut pos = rank - 1;
+        for (k, &(l, b)) in stack.iter().enumerate().rev() {
+            let bv = &self.bv[k];
+            pos = bv.select(b, Self::count(bv, b, l) + pos + 1).unwrap() - l;
+        }
+        Some(pos)
+    }
+
+    pub fn to_text(&self) -> String {
+        (0..self.n).map(|i| self.access(i)).collect()
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.bv.iter().map(|bv| bv.size_of()).sum::<usize>();
+        size += self.char_table.size_of();
+        size
+    }
+
+    pub fn n_chars(&self) -> usize {
+        self.char_table.n_chars()
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+}
+
+#[cfg(test)]
+mod dyn_wt_tests {
+    use crate::dyn_wt::*;
+    use crate::wt::WT;
+    use rand::Rng;
+
+    fn check(wt: &DynWT, model: &[char], alphabet: &str) {
+        assert_eq!(wt.len(), model.len());
+        let mut counts = [0; 128];
+        for (i, &c) in model.iter().enumerate() {
+            assert_eq!(wt.access(i), c);
+            counts[c as usize] += 1;
+            for a in alphabet.chars() {
+                assert_eq!(wt.rank(a, i), counts[a as usize]);
+            }
+            assert_eq!(wt.select(c, counts[c as usize]), Some(i));
+        }
+        for a in alphabet.chars() {
+            assert_eq!(wt.select(a, counts[a as usize] + 1), None);
+        }
+    }
+
+    #[test]
+    fn matches_static() {
+        let s = "abracadabra alakazam";
+        let dwt = DynWT::from_text(s);
+        let wt = WT::new(s);
+        for i in 0..s.len() {
+            assert_eq!(dwt.access(i), wt.access(i));
+            for c in "abcdklmrz ".chars() {
+                assert_eq!(dwt.rank(c, i), wt.rank(c, i));
+            }
+        }
+        assert_eq!(dwt.select('a', 3), wt.select('a', 3));
+        assert_eq!(dwt.to_text(), s);
+    }
+
+    #[test]
+    fn insert_remove() {
+        let alphabet = "ACGTN";
+        let chars: Vec<char> = alphabet.chars().collect();
+        let mut rng = rand::thread_rng();
+        let mut wt = DynWT::new(alphabet);
+        let mut model = vec![];
+        for _ in 0..1500 {
+            let i = rng.gen_range(0, model.len() + 1);
+            let c = chars[rng.gen_range(0, chars.len())];
+            wt.insert(i, c);
+            model.insert(i, c);
+        }
+        check(&wt, &model, alphabet);
+
+        for _ in 0..1000 {
+            let i = rng.gen_range(0, model.len());
+            assert_eq!(wt.remove(i), model.remove(i));
+        }
+        check(&wt, &model, alphabet);
+
+        for c in "GATTACA".chars() {
+            wt.push(c);
+            model.push(c);
+        }
+        check(&wt, &model, alphabet);
+    }
+
+    #[test]
+    fn unary_alphabet() {
+        let mut wt = DynWT::new("x");
+        assert!(wt.is_empty());
+        wt.push('x');
+        wt.insert(0, 'x');
+        assert_eq!(wt.rank('x', 1), 2);
+        assert_eq!(wt.select('x', 2), Some(1));
+        assert_eq!(wt.select('x', 3), None);
+        assert_eq!(wt.remove(0), 'x');
+        assert_eq!(wt.len(), 1);
+    }
+}
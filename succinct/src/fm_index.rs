@@ -0,0 +1,238 @@
+use super::bv::{BitVec, IntVec};
+use super::rank_select::RankSupport;
+use super::wt::WT;
+use super::suffix_array::sais;
+use super::math::{cdiv, clog};
+use serde::{Serialize, Deserialize};
+use std::cmp::max;
+
+// Terminator appended to the text before taking the BWT. Must be the
+// smallest symbol and may not occur in the text itself.
+const SENTINEL: u8 = 0;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FMIndex {
+    n: usize,          // length of text + sentinel
+    bwt: WT,
+    c: IntVec,         // C[c] = # of symbols in text smaller than c (129 entries)
+    rate: usize,       // SA/ISA sampling rate
+    sampled: RankSupport, // marks BWT rows whose SA value is sampled
+    sa: IntVec,        // sampled SA values (divided by rate) in row order
+    isa: IntVec,       // rows of suffixes starting at text positions k * rate
+}
+
+impl FMIndex {
+    pub fn new(s: &str) -> Self {
+        Self::with_sample_rate(s, 32)
+    }
+
+    pub fn with_sample_rate(s: &str, rate: usize) -> Self {
+        // Build the FM-index of `s`, keeping every `rate`-th SA and ISA value
+        assert!(s.is_ascii());
+        assert!(rate > 0);
+        let mut text = s.as_bytes().to_vec();
+        assert!(!text.contains(&SENTINEL));
+        text.push(SENTINEL);
+        let n = text.len();
+
+        let sa = sais(&text);
+
+        // BWT[i] = T[SA[i] - 1]
+        let bwt: String = (0..n).map(|i| sa.get_int(i) as usize)
+                                .map(|p| text[(p + n - 1) % n] as char)
+                                .collect();
+
+        // C array over ascii
+        let mut counts = [0_u32; 129];
+        for &b in text.iter() {
+            counts[b as usize + 1] += 1;
+        }
+        for i in 1..counts.len() {
+            counts[i] += counts[i - 1];
+        }
+        let c = IntVec::from_vec(&counts.to_vec(), max(clog(n + 1), 1));
+
+        // Sample SA at text positions divisible by rate
+        let n_samples = cdiv(n, rate);
+        let w = max(clog(n_samples + 1), 1);
+        let mut sampled = BitVec::new(n);
+        let mut sa_samples = IntVec::new(w, n_samples);
+        let mut isa = IntVec::new(max(clog(n + 1), 1), n_samples);
+        let mut k = 0;
+        for i in 0..n {
+            let p = sa.get_int(i) as usize;
+            if p.is_multiple_of(rate) {
+                sampled.set(i, true);
+                sa_samples.set_int(k, (p / rate) as u32);
+                isa.set_int(p / rate, i as u32);
+                k += 1;
+            }
+        }
+
+        Self {
+            n,
+            bwt: WT::new(&bwt),
+            c,
+            rate,
+            sampled: RankSupport::new(sampled),
+            sa: sa_samples,
+            isa,
+        }
+    }
+
+    pub fn count(&self, pattern: &str) -> usize {
+        // Number of occurrences of pattern in the text
+        let (sp, ep) = self.range(pattern);
+        ep - sp
+    }
+
+    pub fn locate(&self, pattern: &str) -> Vec<usize> {
+        // Sorted text positions of all occurrences of pattern
+        let (sp, ep) = self.range(pattern);
+        let mut positions: Vec<usize> = (sp..ep).map(|i| self.sa_at(i)).collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    pub fn extract(&self, i: usize, j: usize) -> String {
+        // Text substring [i, j)
+        assert!(i <= j);
+        assert!(j < self.n);
+
+        // Start from the closest sampled suffix at or after j and step back
+        let k = cdiv(j, self.rate);
+        let (mut row, mut pos) = if k * self.rate < self.n - 1 {
+            (self.isa.get_int(k) as usize, k * self.rate)
+        } else {
+            (0, self.n - 1) // the sentinel suffix is always the first row
+        };
+
+        let mut rev = Vec::with_capacity(j - i);
+        while pos > i {
+            let c = self.bwt.access(row);
+            if pos <= j {
+                rev.push(c);
+            }
+            row = self.lf(row, c);
+            pos -= 1;
+        }
+        rev.iter().rev().collect()
+    }
+
+    fn range(&self, pattern: &str) -> (usize, usize) {
+        // Backward search: BWT rows [sp, ep) prefixed by pattern
+        let mut sp = 0;
+        let mut ep = self.n;
+        for c in pattern.chars().rev() {
+            if !c.is_ascii() || c as u8 == SENTINEL { return (0, 0) }
+            let c_i = c as usize;
+            let c_c = self.c.get_int(c_i) as usize;
+            if c_c == self.c.get_int(c_i + 1) as usize { return (0, 0) }
+
+            sp = c_c + self.rank(c, sp);
+            ep = c_c + self.rank(c, ep);
+            if sp >= ep { return (0, 0) }
+        }
+        (sp, ep)
+    }
+
+    fn rank(&self, c: char, i: usize) -> usize {
+        // Occurrences of c in BWT[0, i)
+        if i == 0 { 0 } else { self.bwt.rank(c, i - 1) }
+    }
+
+    fn lf(&self, i: usize, c: char) -> usize {
+        // LF mapping of row i, where c = BWT[i]
+        self.c.get_int(c as usize) as usize + self.bwt.rank(c, i) - 1
+    }
+
+    fn sa_at(&self, i: usize) -> usize {
+        // Recover SA[i] by walking LF to the nearest sampled row
+        let mut row = i;
+        let mut steps = 0;
+        while !self.sampled.get(row) {
+            row = self.lf(row, self.bwt.access(row));
+            steps += 1;
+        }
+        let k = self.sampled.rank1(row) - 1;
+        self.sa.get_int(k) as usize * self.rate + steps
+    }
+
+    pub fn len(&self) -> usize {
+        // Length of the indexed text (without sentinel)
+        self.n - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.bwt.size_of();
+        size += self.c.size_of();
+        size += self.sampled.size_of();
+        size += self.sa.size_of();
+        size += self.isa.size_of();
+        size
+    }
+}
+
+#[cfg(test)]
+mod fm_tests {
+    use crate::fm_index::*;
+
+    fn naive_locate(s: &str, p: &str) -> Vec<usize> {
+        (0..s.len()).filter(|&i| s[i..].starts_with(p)).collect()
+    }
+
+    #[test]
+    fn count() {
+        let fm = FMIndex::new("abracadabra");
+        assert_eq!(fm.count("a"), 5);
+        assert_eq!(fm.count("abra"), 2);
+        assert_eq!(fm.count("bra"), 2);
+        assert_eq!(fm.count("cad"), 1);
+        assert_eq!(fm.count("abracadabra"), 1);
+        assert_eq!(fm.count("abrab"), 0);
+        assert_eq!(fm.count("z"), 0);
+    }
+
+    #[test]
+    fn locate() {
+        let s = "tomorrow and tomorrow and tomorrow";
+        for rate in &[1, 3, 32] {
+            let fm = FMIndex::with_sample_rate(s, *rate);
+            for p in &["tomorrow", "o", "and", " a", "w", "rrow and t", "x"] {
+                assert_eq!(fm.locate(p), naive_locate(s, p));
+            }
+        }
+    }
+
+    #[test]
+    fn extract() {
+        let s = "yabadabadoo";
+        for rate in &[1, 2, 5, 32] {
+            let fm = FMIndex::with_sample_rate(s, *rate);
+            for i in 0..s.len() {
+                for j in i..s.len() + 1 {
+                    assert_eq!(fm.extract(i, j), &s[i..j]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate() {
+        let fm = FMIndex::new("aaaaa");
+        assert_eq!(fm.count("aa"), 4);
+        assert_eq!(fm.locate("aaaaa"), vec![0]);
+        assert_eq!(fm.extract(1, 4), "aaa");
+
+        let fm = FMIndex::new("");
+        assert!(fm.is_empty());
+        assert_eq!(fm.count("a"), 0);
+        assert_eq!(fm.extract(0, 0), "");
+    }
+}
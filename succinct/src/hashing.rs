@@ -0,0 +1,325 @@
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::max;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+
+pub trait FilterHasher: Debug + Clone + Default {
+    // Hash function used by a filter, built from 4 seeds per probe. Items
+    // are fed to the returned `Hasher` through `std::hash::Hash`.
+    type State: Hasher;
+    fn with_seeds(&self, seeds: &[u64]) -> Self::State;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SeaHash;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MultiplyShift;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CanonicalKmer;
+
+// The hashers have no state, so they serialize as a name that is checked on
+// load: a filter built with one hasher fails to deserialize as another.
+macro_rules! tagged_hasher {
+    ($hasher:ident, $tag:expr) => {
+        impl Serialize for $hasher {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str($tag)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $hasher {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let tag = String::deserialize(deserializer)?;
+                if tag == $tag {
+                    Ok($hasher)
+                } else {
+                    Err(D::Error::custom(format!("filter uses hasher {}, not {}", tag, $tag)))
+                }
+            }
+        }
+    };
+}
+
+tagged_hasher!(SeaHash, "seahash");
+tagged_hasher!(MultiplyShift, "multiply-shift");
+tagged_hasher!(CanonicalKmer, "canonical-kmer");
+
+pub struct MultiplyShiftHasher {
+    // Integers written are folded into acc; finish() is (a acc + b) >> 64
+    // over 128 bits (Dietzfelbinger), a random odd a and random b
+    acc: u64,
+    a: u128,
+    b: u128,
+}
+
+pub struct CanonicalKmerHasher {
+    // SeaHash of the smaller of a k-mer and its reverse complement
+    inner: seahash::SeaHasher,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HashScheme {
+    // How a filter derives its k probe hashes from an item
+    #[default]
+    Seeded,        // k independently seeded hashes of the item
+    DoubleHashing, // two hashes, expanded by enhanced double hashing
+}
+
+impl FilterHasher for SeaHash {
+    type State = seahash::SeaHasher;
+
+    fn with_seeds(&self, seeds: &[u64]) -> Self::State {
+        seahash::SeaHasher::with_seeds(seeds[0], seeds[1], seeds[2], seeds[3])
+    }
+}
+
+impl FilterHasher for MultiplyShift {
+    // Fast universal hash for integer keys. Every integer is widened to
+    // u64 and bytes are read as little endian u64 chunks, so it is portable.
+    type State = MultiplyShiftHasher;
+
+    fn with_seeds(&self, seeds: &[u64]) -> Self::State {
+        MultiplyShiftHasher {
+            acc: 0,
+            a: ((seeds[0] as u128) << 64 | seeds[1] as u128) | 1,
+            b: (seeds[2] as u128) << 64 | seeds[3] as u128,
+        }
+    }
+}
+
+impl Hasher for MultiplyShiftHasher {
+    fn finish(&self) -> u64 {
+        (self.a.wrapping_mul(self.acc as u128).wrapping_add(self.b) >> 64) as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0_u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, x: u8) {
+        self.write_u64(x as u64);
+    }
+
+    fn write_u16(&mut self, x: u16) {
+        self.write_u64(x as u64);
+    }
+
+    fn write_u32(&mut self, x: u32) {
+        self.write_u64(x as u64);
+    }
+
+    fn write_u64(&mut self, x: u64) {
+        // A single integer key hashes to exactly (a x + b) >> 64
+        self.acc = self.acc.rotate_left(31) ^ x;
+    }
+
+    fn write_usize(&mut self, x: usize) {
+        self.write_u64(x as u64);
+    }
+}
+
+impl FilterHasher for CanonicalKmer {
+    // A k-mer and its reverse complement hash the same. The k-mer has to
+    // arrive in one `write` call, as it does for str, String, [u8] and
+    // RawBytes keys; A/C/G/T (either case) are complemented, other bytes
+    // are kept.
+    type State = CanonicalKmerHasher;
+
+    fn with_seeds(&self, seeds: &[u64]) -> Self::State {
+        CanonicalKmerHasher { inner: SeaHash.with_seeds(seeds) }
+    }
+}
+
+impl Hasher for CanonicalKmerHasher {
+    fn finish(&self) -> u64 {
+        self.inner.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let rc: Vec<u8> = bytes.iter().rev().map(|&c| complement(c)).collect();
+        if rc.as_slice() < bytes {
+            self.inner.write(&rc);
+        } else {
+            self.inner.write(bytes);
+        }
+    }
+
+    fn write_usize(&mut self, x: usize) {
+        // Length prefixes are the same for both strands
+        self.inner.write_u64(x as u64);
+    }
+}
+
+fn complement(c: u8) -> u8 {
+    match c {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' => b'A',
+        b'a' => b't',
+        b'c' => b'g',
+        b'g' => b'c',
+        b't' => b'a',
+        _ => c,
+    }
+}
+
+pub struct Probes<'a, F, H> {
+    // Iterator over the k probe hashes of an item
+    hasher: &'a F,
+    scheme: HashScheme,
+    seeds: &'a [u64],
+    item: &'a H,
+    i: u64,
+    k: u64,
+    h1: u64,
+    h2: u64,
+}
+
+pub fn probes<'a, F: FilterHasher, H: Hash>(hasher: &'a F, scheme: HashScheme, seeds: &'a [u64],
+                                            k: usize, item: &'a H) -> Probes<'a, F, H> {
+    // Seeded needs 4k seeds, DoubleHashing the first 8 (see n_seeds): h1
+    // and h2 are two independently seeded hashes of the item
+    let (h1, h2) = match scheme {
+        HashScheme::Seeded => (0, 0),
+        HashScheme::DoubleHashing => (seeded_hash(hasher, seeds, 0, item), seeded_hash(hasher, seeds, 1, item)),
+    };
+    Probes { hasher, scheme, seeds, item, i: 0, k: k as u64, h1, h2 }
+}
+
+impl<'a, F: FilterHasher, H: Hash> Iterator for Probes<'a, F, H> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.i == self.k { return None }
+        let i = self.i;
+        self.i += 1;
+        Some(match self.scheme {
+            HashScheme::Seeded => seeded_hash(self.hasher, self.seeds, i as usize, self.item),
+            // Kirsch-Mitzenmacher with the cubic term of Dillinger & Manolios:
+            // h1 + i h2 + (i^3 - i) / 6, so probes differ even when h2 = 0 mod m
+            HashScheme::DoubleHashing => {
+                let cube = i.wrapping_mul(i).wrapping_mul(i).wrapping_sub(i) / 6;
+                self.h1.wrapping_add(i.wrapping_mul(self.h2)).wrapping_add(cube)
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawBytes<'a>(pub &'a [u8]);
+
+impl Hash for RawBytes<'_> {
+    // Hashes exactly the given bytes. `Hash` for str, [u8] or usize adds
+    // length prefixes or native-width integers, so only byte keys hash the
+    // same on every platform: with the default SeaHash, a probe hash is the
+    // `finish()` of a `seahash::SeaHasher::with_seeds(s0, s1, s2, s3)` fed
+    // the bytes in one `write` call, with the seeds drawn from
+    // `seeds_from(seed, 4k)`.
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        state.write(self.0);
+    }
+}
+
+pub fn reduce(h: u64, n: usize) -> usize {
+    // h mod n over all 64 bits, so 32 bit targets pick the same position
+    (h % n as u64) as usize
+}
+
+pub fn n_seeds(k: usize) -> usize {
+    // Seeds `probes` reads for k hashes under either scheme
+    4 * max(k, 2)
+}
+
+pub fn seeds_from(seed: u64, n: usize) -> Vec<u64> {
+    // First n outputs of the SplitMix64 generator started at seed
+    (0..n as u64).map(|i| mix64(seed.wrapping_add(i.wrapping_mul(0x9e37_79b9_7f4a_7c15)))).collect()
+}
+
+pub fn seeded_hash<F: FilterHasher, H: Hash>(hasher: &F, seeds: &[u64], i: usize, item: &H) -> u64 {
+    // i-th hash of item, seeded by seeds[4i..4i + 4]
+    let mut state = hasher.with_seeds(&seeds[i * 4..i * 4 + 4]);
+    item.hash(&mut state);
+    state.finish()
+}
+
+pub fn mix64(x: u64) -> u64 {
+    // SplitMix64 finalizer: a bijection on u64 with good avalanche
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod hashing_tests {
+    use crate::hashing::*;
+
+    #[test]
+    fn probes_per_scheme() {
+        let seeds: Vec<u64> = (0..12).collect();
+        let seeded: Vec<u64> = probes(&SeaHash, HashScheme::Seeded, &seeds, 3, &"x").collect();
+        assert_eq!(seeded.len(), 3);
+        assert_eq!(seeded[2], seeded_hash(&SeaHash, &seeds, 2, &"x"));
+
+        let dh: Vec<u64> = probes(&SeaHash, HashScheme::DoubleHashing, &seeds, 5, &"x").collect();
+        assert_eq!(dh.len(), 5);
+        assert_eq!(dh[0], seeded_hash(&SeaHash, &seeds, 0, &"x"));
+        assert_eq!(dh[1], dh[0].wrapping_add(seeded_hash(&SeaHash, &seeds, 1, &"x")));
+        // k = 1 still reads the seeds of the second hash
+        assert_eq!(probes(&SeaHash, HashScheme::DoubleHashing, &seeds_from(1, n_seeds(1)), 1, &"x").count(), 1);
+        let mut sorted = dh.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 5);
+    }
+
+    #[test]
+    fn portable() {
+        // SplitMix64 reference outputs for seed 0
+        let seeds = seeds_from(0, 4);
+        assert_eq!(seeds[0], 0xe220_a839_7b1d_cdaf);
+        assert_eq!(seeds[1], 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(seeds_from(42, 8)[..4], seeds_from(42, 4)[..]);
+
+        // Known answers pin the byte hash across platforms and versions
+        let seeds = seeds_from(42, 4);
+        let h = seeded_hash(&SeaHash, &seeds, 0, &RawBytes(b"ACGT"));
+        assert_eq!(h, 0xcf27_fc8b_cf7e_9185);
+        assert_ne!(h, seeded_hash(&SeaHash, &seeds, 0, &RawBytes(b"ACGA")));
+    }
+
+    #[test]
+    fn hashers() {
+        let seeds = seeds_from(1, 4);
+        // Multiply-shift on one integer is (a x + b) >> 64
+        let a = ((seeds[0] as u128) << 64 | seeds[1] as u128) | 1;
+        let b = (seeds[2] as u128) << 64 | seeds[3] as u128;
+        let expected = (a.wrapping_mul(12345).wrapping_add(b) >> 64) as u64;
+        assert_eq!(seeded_hash(&MultiplyShift, &seeds, 0, &12345_u64), expected);
+        assert_eq!(seeded_hash(&MultiplyShift, &seeds, 0, &12345_u32), expected);
+        assert_eq!(seeded_hash(&MultiplyShift, &seeds, 0, &12345_usize), expected);
+
+        let h = |s: &str| seeded_hash(&CanonicalKmer, &seeds, 0, &s);
+        assert_eq!(h("ACGTTG"), h("CAACGT"));
+        assert_eq!(h("aacc"), h("ggtt"));
+        assert_ne!(h("ACGTTG"), h("ACGTTC"));
+        assert_eq!(seeded_hash(&CanonicalKmer, &seeds, 0, &RawBytes(b"GGGA")),
+                   seeded_hash(&CanonicalKmer, &seeds, 0, &RawBytes(b"TCCC")));
+    }
+
+    #[test]
+    fn hasher_tags() {
+        let encoded = bincode::serialize(&MultiplyShift).unwrap();
+        assert_eq!(bincode::deserialize::<MultiplyShift>(&encoded).unwrap(), MultiplyShift);
+        assert!(bincode::deserialize::<SeaHash>(&encoded).is_err());
+        assert!(bincode::deserialize::<CanonicalKmer>(&encoded).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use super::bv::{BitVec, IntVec};
+use super::rank_select::RankSupport;
+use super::rmq::Rmq;
+use super::math::clog;
+use serde::{Serialize, Deserialize};
+use std::cmp::min;
+
+// LCP[0] = 0 and LCP[i] = lcp(T[SA[i-1]..], T[SA[i]..]) for i > 0.
+
+pub trait Lcp {
+    fn lcp(&self, i: usize) -> usize;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn range_min(&self, i: usize, j: usize) -> usize;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LcpArray {
+    lcp: IntVec,
+    rmq: Rmq,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressedLcp {
+    // Sadakane's 2n bit encoding of PLCP: bit PLCP[i] + 2i is set. The
+    // suffix array is not stored; LCP queries go through `with_sa`.
+    n: usize,
+    plcp: RankSupport,
+    rmq: Rmq,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompressedLcpRef<'a> {
+    // CompressedLcp paired with the suffix array it was built from
+    lcp: &'a CompressedLcp,
+    sa: &'a IntVec,
+}
+
+impl LcpArray {
+    pub fn new(text: &[u8], sa: &IntVec) -> Self {
+        Self::from_intvec(kasai(text, sa))
+    }
+
+    pub fn from_plcp(text: &[u8], sa: &IntVec) -> Self {
+        // Same array, built from PLCP without the inverse suffix array
+        Self::from_intvec(lcp_from_plcp(text, sa))
+    }
+
+    pub fn from_intvec(lcp: IntVec) -> Self {
+        let rmq = Rmq::new(&lcp);
+        Self { lcp, rmq }
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        std::mem::size_of::<Self>() + self.lcp.size_of() + self.rmq.size_of()
+    }
+}
+
+impl Lcp for LcpArray {
+    fn lcp(&self, i: usize) -> usize {
+        self.lcp.get_int(i) as usize
+    }
+
+    fn len(&self) -> usize {
+        self.lcp.len()
+    }
+
+    fn range_min(&self, i: usize, j: usize) -> usize {
+        self.lcp(self.rmq.rmq(i, j))
+    }
+}
+
+impl CompressedLcp {
+    pub fn new(text: &[u8], sa: &IntVec) -> Self {
+        let n = sa.len();
+        let plcp = plcp(text, sa);
+        let mut bv = BitVec::new(2 * n + 1); // + 1 so RankSupport is never empty
+        for i in 0..n {
+            bv.set(plcp.get_int(i) as usize + 2 * i, true);
+        }
+        Self {
+            n,
+            rmq: Rmq::build(n, |i| plcp.get_int(sa.get_int(i) as usize)),
+            plcp: RankSupport::new(bv),
+        }
+    }
+
+    pub fn plcp(&self, p: usize) -> usize {
+        // PLCP[p] = LCP[ISA[p]], the LCP of the suffix starting at p
+        self.plcp.select1(p + 1).unwrap() - 2 * p
+    }
+
+    pub fn with_sa<'a>(&'a self, sa: &'a IntVec) -> CompressedLcpRef<'a> {
+        // LCP queries by suffix array rank; `sa` must be the one passed to new
+        assert_eq!(sa.len(), self.n);
+        CompressedLcpRef { lcp: self, sa }
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        std::mem::size_of::<Self>() + self.plcp.size_of() + self.rmq.size_of()
+    }
+}
+
+impl Lcp for CompressedLcpRef<'_> {
+    fn lcp(&self, i: usize) -> usize {
+        self.lcp.plcp(self.sa.get_int(i) as usize)
+    }
+
+    fn len(&self) -> usize {
+        self.lcp.n
+    }
+
+    fn range_min(&self, i: usize, j: usize) -> usize {
+        self.lcp(self.lcp.rmq.rmq(i, j))
+    }
+}
+
+fn width(n: usize) -> usize {
+    // Bits for values in [0, n]
+    if n > 0 { clog(n + 1) } else { 1 }
+}
+
+pub fn kasai(text: &[u8], sa: &IntVec) -> IntVec {
+    // Kasai et al. "Linear-Time Longest-Common-Prefix Computation in Suffix
+    // Arrays and Its Applications". Uses the inverse suffix array.
+    let n = sa.len();
+    assert_eq!(n, text.len());
+    let mut isa = IntVec::new(width(n), n);
+    for i in 0..n {
+        isa.set_int(sa.get_int(i) as usize, i as u32);
+    }
+
+    let mut lcp = IntVec::new(width(n), n);
+    let mut h = 0;
+    for i in 0..n {
+        let r = isa.get_int(i) as usize;
+        if r == 0 {
+            h = 0;
+            continue;
+        }
+        let j = sa.get_int(r - 1) as usize;
+        h += common_prefix(&text[i + h..], &text[j + h..]);
+        lcp.set_int(r, h as u32);
+        h = h.saturating_sub(1);
+    }
+    lcp
+}
+
+pub fn plcp(text: &[u8], sa: &IntVec) -> IntVec {
+    // Kärkkäinen, Manzini and Puglisi. "Permuted Longest-Common-Prefix Array"
+    // PLCP[i] = LCP[ISA[i]], computed in text order from the Φ array, which
+    // is overwritten in place.
+    let n = sa.len();
+    assert_eq!(n, text.len());
+    let mut phi = IntVec::new(width(n), n);
+    if n == 0 { return phi }
+    phi.set_int(sa.get_int(0) as usize, n as u32); // no predecessor
+    for i in 1..n {
+        phi.set_int(sa.get_int(i) as usize, sa.get_int(i - 1));
+    }
+
+    let mut h = 0;
+    for i in 0..n {
+        let j = phi.get_int(i) as usize;
+        if j == n {
+            h = 0;
+        } else {
+            h += common_prefix(&text[i + h..], &text[j + h..]);
+        }
+        phi.set_int(i, h as u32);
+        h = h.saturating_sub(1);
+    }
+    phi
+}
+
+fn lcp_from_plcp(text: &[u8], sa: &IntVec) -> IntVec {
+    // LCP array via PLCP, without the inverse suffix array
+    let n = sa.len();
+    let plcp = plcp(text, sa);
+    let mut lcp = IntVec::new(width(n), n);
+    for i in 0..n {
+        lcp.set_int(i, plcp.get_int(sa.get_int(i) as usize));
+    }
+    lcp
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    let m = min(a.len(), b.len());
+    (0..m).find(|&k| a[k] != b[k]).unwrap_or(m)
+}
+
+#[cfg(test)]
+mod lcp_tests {
+    use crate::lcp::*;
+    use crate::suffix_array::sais;
+    use rand::Rng;
+
+    fn naive(text: &[u8], sa: &IntVec) -> Vec<u32> {
+        let mut lcp = vec![0; sa.len()];
+        for i in 1..sa.len() {
+            let a = &text[sa.get_int(i - 1) as usize..];
+            let b = &text[sa.get_int(i) as usize..];
+            lcp[i] = common_prefix(a, b) as u32;
+        }
+        lcp
+    }
+
+    #[test]
+    fn banana() {
+        let text = b"banana";
+        let sa = sais(text);
+        let expected = vec![0, 1, 3, 0, 0, 2];
+        assert_eq!(kasai(text, &sa).to_vec(), expected);
+        assert_eq!(lcp_from_plcp(text, &sa).to_vec(), expected);
+        assert_eq!(plcp(text, &sa).to_vec(), vec![0, 3, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn compressed() {
+        for s in &["abracadabra", "mississippi", "aaaaaaaa", "a", ""] {
+            let text = s.as_bytes();
+            let sa = sais(text);
+            let expected = naive(text, &sa);
+            let lcp = LcpArray::new(text, &sa);
+            let compressed = CompressedLcp::new(text, &sa);
+            let clcp = compressed.with_sa(&sa);
+            assert_eq!(lcp.len(), clcp.len());
+            for (i, &e) in expected.iter().enumerate() {
+                assert_eq!(lcp.lcp(i), e as usize);
+                assert_eq!(clcp.lcp(i), e as usize);
+                assert_eq!(compressed.plcp(sa.get_int(i) as usize), e as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn range_min() {
+        let text = b"tomorrow and tomorrow and tomorrow";
+        let sa = sais(text);
+        let expected = naive(text, &sa);
+        let lcp = LcpArray::new(text, &sa);
+        let compressed = CompressedLcp::new(text, &sa);
+        let clcp = compressed.with_sa(&sa);
+        for i in 0..lcp.len() {
+            for j in i..lcp.len() {
+                let m = *expected[i..j + 1].iter().min().unwrap() as usize;
+                assert_eq!(lcp.range_min(i, j), m);
+                assert_eq!(clcp.range_min(i, j), m);
+            }
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let mut rng = rand::thread_rng();
+        let text: Vec<u8> = (0..100000).map(|_| b"acgt"[rng.gen_range(0, 4)]).collect();
+        let sa = sais(&text);
+        let lcp = LcpArray::new(&text, &sa);
+        let clcp = CompressedLcp::new(&text, &sa);
+        assert_eq!(LcpArray::from_plcp(&text, &sa).lcp.to_vec(), lcp.lcp.to_vec());
+        assert!(clcp.size_of() < lcp.size_of() / 2);
+    }
+
+    #[test]
+    fn fuzz() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let n = rng.gen_range(1, 300);
+            let text: Vec<u8> = (0..n).map(|_| b"acgt"[rng.gen_range(0, 4)]).collect();
+            let sa = sais(&text);
+            let expected = naive(&text, &sa);
+            assert_eq!(kasai(&text, &sa).to_vec(), expected);
+            assert_eq!(lcp_from_plcp(&text, &sa).to_vec(), expected);
+        }
+    }
+}
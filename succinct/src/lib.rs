@@ -1,5 +1,22 @@
 pub mod bv;
 pub mod rank_select;
 pub mod wt;
+pub mod dyn_bv;
+pub mod dyn_wt;
 pub mod math;
-pub mod bloom_filter;
\ No newline at end of file
+pub mod space;
+pub mod hashing;
+pub mod bloom_filter;
+pub mod scalable_bloom_filter;
+pub mod cuckoo_filter;
+pub mod quotient_filter;
+pub mod xor_filter;
+pub mod suffix_array;
+pub mod lcp;
+pub mod rmq;
+pub mod bp;
+pub mod louds;
+pub mod trie;
+pub mod seq;
+pub mod dna;
+pub mod fm_index;
\ No newline at end of file
@@ -0,0 +1,253 @@
+use super::bv::BitVec;
+use super::rank_select::RankSupport;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Louds {
+    // Level-order unary degree sequence (Jacobson). A super root "10" is
+    // followed by 1^d 0 for every node of degree d in BFS order, for 2n + 1
+    // bits. Nodes are identified by their BFS id (root = 0); the i-th 1 in
+    // the sequence refers to node i - 1.
+    n: usize,
+    rs: RankSupport,
+}
+
+#[derive(Debug)]
+pub struct LoudsBuilder {
+    children: Vec<Vec<usize>>,
+    order: Vec<usize>, // BFS id -> input id
+    bfs: Vec<usize>,   // input id -> BFS id
+}
+
+impl LoudsBuilder {
+    pub fn from_parents(parents: &[Option<usize>]) -> Self {
+        // Tree given by the parent of every node (None for the root).
+        // Children are ordered by their input id.
+        let mut children = vec![vec![]; parents.len()];
+        let mut root = None;
+        for (v, p) in parents.iter().enumerate() {
+            match p {
+                Some(p) => children[*p].push(v),
+                None => {
+                    assert!(root.is_none(), "more than one root");
+                    root = Some(v);
+                }
+            }
+        }
+        Self::from_children(&children, root.expect("no root"))
+    }
+
+    pub fn from_children(children: &[Vec<usize>], root: usize) -> Self {
+        // Tree given by ordered adjacency lists
+        let n = children.len();
+        let mut order = Vec::with_capacity(n);
+        let mut bfs = vec![usize::MAX; n];
+        let mut queue = VecDeque::new();
+        queue.push_back(root);
+        while let Some(v) = queue.pop_front() {
+            assert_eq!(bfs[v], usize::MAX, "not a tree");
+            bfs[v] = order.len();
+            order.push(v);
+            queue.extend(children[v].iter());
+        }
+        assert_eq!(order.len(), n, "not connected");
+        Self {
+            children: children.to_vec(),
+            order,
+            bfs,
+        }
+    }
+
+    pub fn bfs_id(&self, v: usize) -> usize {
+        // BFS id (LOUDS node) of input node v
+        self.bfs[v]
+    }
+
+    pub fn bfs_order(&self) -> &[usize] {
+        // Input ids in BFS order
+        &self.order
+    }
+
+    pub fn finish(&self) -> Louds {
+        let n = self.order.len();
+        let mut bv = BitVec::new(2 * n + 1);
+        bv.set(0, true);
+        let mut p = 2;
+        for &v in self.order.iter() {
+            for _ in self.children[v].iter() {
+                bv.set(p, true);
+                p += 1;
+            }
+            p += 1;
+        }
+        assert_eq!(p, 2 * n + 1);
+        Louds {
+            n,
+            rs: RankSupport::new(bv),
+        }
+    }
+}
+
+impl Louds {
+    pub fn from_parents(parents: &[Option<usize>]) -> Self {
+        LoudsBuilder::from_parents(parents).finish()
+    }
+
+    pub fn from_children(children: &[Vec<usize>], root: usize) -> Self {
+        LoudsBuilder::from_children(children, root).finish()
+    }
+
+    pub fn n_nodes(&self) -> usize {
+        self.n
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn position(&self, v: usize) -> usize {
+        // Position of the 1 referring to node v
+        assert!(v < self.n);
+        self.rs.select1(v + 1).unwrap()
+    }
+
+    pub fn node_at(&self, p: usize) -> usize {
+        // Node referred to by the 1 at position p
+        assert!(self.rs.get(p));
+        self.rs.rank1(p) - 1
+    }
+
+    fn start(&self, v: usize) -> usize {
+        // First bit of node v's unary degree
+        self.rs.select0(v + 1).unwrap() + 1
+    }
+
+    pub fn degree(&self, v: usize) -> usize {
+        assert!(v < self.n);
+        self.rs.select0(v + 2).unwrap() - self.start(v)
+    }
+
+    pub fn is_leaf(&self, v: usize) -> bool {
+        !self.rs.get(self.start(v))
+    }
+
+    pub fn first_child(&self, v: usize) -> Option<usize> {
+        self.child(v, 0)
+    }
+
+    pub fn child(&self, v: usize, i: usize) -> Option<usize> {
+        // i-th child of v (0-based)
+        assert!(v < self.n);
+        let p = self.start(v) + i;
+        if p < self.rs.select0(v + 2).unwrap() {
+            Some(self.node_at(p))
+        } else {
+            None
+        }
+    }
+
+    pub fn children(&self, v: usize) -> std::ops::Range<usize> {
+        // Children of a node have consecutive BFS ids
+        let start = self.start(v);
+        let first = self.rs.rank1(start - 1);
+        first..first + self.degree(v)
+    }
+
+    pub fn next_sibling(&self, v: usize) -> Option<usize> {
+        if v == 0 { return None }
+        let p = self.position(v);
+        if self.rs.get(p + 1) { Some(v + 1) } else { None }
+    }
+
+    pub fn parent(&self, v: usize) -> Option<usize> {
+        if v == 0 { return None }
+        Some(self.rs.rank0(self.position(v)) - 1)
+    }
+
+    pub fn depth(&self, v: usize) -> usize {
+        let mut d = 0;
+        let mut v = v;
+        while let Some(p) = self.parent(v) {
+            v = p;
+            d += 1;
+        }
+        d
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        std::mem::size_of::<Self>() + self.rs.size_of()
+    }
+}
+
+#[cfg(test)]
+mod louds_tests {
+    use crate::louds::*;
+
+    //        0
+    //      / | \
+    //     1  2  3
+    //    / \    |
+    //   4   5   6
+    //           |
+    //           7
+    fn tree() -> Louds {
+        let children = vec![vec![1, 2, 3], vec![4, 5], vec![], vec![6],
+                            vec![], vec![], vec![7], vec![]];
+        Louds::from_children(&children, 0)
+    }
+
+    #[test]
+    fn navigation() {
+        let t = tree();
+        assert_eq!(t.n_nodes(), 8);
+        assert_eq!(t.degree(0), 3);
+        assert_eq!(t.degree(1), 2);
+        assert_eq!(t.degree(2), 0);
+        assert_eq!(t.first_child(0), Some(1));
+        assert_eq!(t.first_child(3), Some(6));
+        assert_eq!(t.first_child(2), None);
+        assert_eq!(t.child(0, 2), Some(3));
+        assert_eq!(t.child(0, 3), None);
+        assert_eq!(t.child(1, 1), Some(5));
+        assert_eq!(t.children(1), 4..6);
+        assert_eq!(t.children(5), 7..7);
+        assert_eq!(t.next_sibling(1), Some(2));
+        assert_eq!(t.next_sibling(3), None);
+        assert_eq!(t.next_sibling(4), Some(5));
+        assert_eq!(t.next_sibling(5), None);
+        assert!(t.is_leaf(7));
+        assert!(!t.is_leaf(6));
+    }
+
+    #[test]
+    fn parent_depth() {
+        let t = tree();
+        let parents = [None, Some(0), Some(0), Some(0), Some(1), Some(1), Some(3), Some(6)];
+        let depths = [0, 1, 1, 1, 2, 2, 2, 3];
+        for v in 0..t.n_nodes() {
+            assert_eq!(t.parent(v), parents[v]);
+            assert_eq!(t.depth(v), depths[v]);
+            assert_eq!(t.node_at(t.position(v)), v);
+        }
+    }
+
+    #[test]
+    fn from_parents() {
+        // Input ids are not in BFS order: root is 3
+        let parents = [Some(3), Some(0), Some(3), None, Some(2)];
+        let b = LoudsBuilder::from_parents(&parents);
+        assert_eq!(b.bfs_order(), &[3, 0, 2, 1, 4]);
+        assert_eq!(b.bfs_id(3), 0);
+        assert_eq!(b.bfs_id(4), 4);
+        let t = b.finish();
+        for (v, p) in parents.iter().enumerate() {
+            assert_eq!(t.parent(b.bfs_id(v)), p.map(|p| b.bfs_id(p)));
+        }
+
+        let t = Louds::from_parents(&[None]);
+        assert_eq!(t.degree(0), 0);
+        assert_eq!(t.parent(0), None);
+    }
+}
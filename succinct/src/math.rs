@@ -19,7 +19,7 @@ pub fn flog(x: usize) -> usize {
 }
 
 pub fn cdiv(a: usize, b: usize) -> usize {
-    if a % b == 0 {
+    if a.is_multiple_of(b) {
         a / b
     } else {
         (a / b) + 1
@@ -0,0 +1,457 @@
+use super::bv::{BitVec, IntVec};
+use super::bloom_filter::{FilterError, Query, MQ};
+use super::hashing::{seeded_hash, seeds_from, FilterHasher, SeaHash};
+use super::math::clog;
+use super::space::{SpaceBreakdown, SpaceUsage};
+
+use rand::Rng;
+use std::cmp::{max, min};
+use std::hash::Hash;
+use serde::{Serialize, Deserialize};
+
+// Inserts fail (or trigger a resize) above this load factor
+const MAX_LOAD: f32 = 0.95;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotientFilter<F = SeaHash> {
+    // Quotient filter (Bender et al.). A p = q + r bit fingerprint is split
+    // into a quotient (its home slot among 2^q) and an r bit remainder.
+    // Remainders with the same quotient form a sorted run, runs are sorted
+    // by quotient and shifted right by linear probing (wrapping around).
+    // Per slot: occupied (some fingerprint has this quotient), continuation
+    // (not the first remainder of its run), shifted (not in its home slot).
+    //
+    // Updates decode the maximal block of non-empty slots around the home
+    // slot into (quotient, remainder) pairs, change them and write the
+    // block back in canonical layout. One slot is always kept empty.
+    q: usize,
+    r: usize,
+    rem: IntVec,
+    occupied: BitVec,
+    continuation: BitVec,
+    shifted: BitVec,
+    n_items: usize,
+    seeds: Vec<u64>,
+    seed: u64,
+    hasher: F,
+}
+
+impl<F: FilterHasher> MQ for QuotientFilter<F> {
+    fn insert<H: Hash>(&mut self, item: &H) {
+        // Doubles the table when it is too full
+        if self.try_insert(item).is_err() {
+            self.resize(2 * self.capacity()).expect("quotient filter is full");
+            self.try_insert(item).expect("quotient filter is full");
+        }
+    }
+}
+
+impl<F: FilterHasher> Query for QuotientFilter<F> {
+    fn query<H: Hash>(&self, item: &H) -> bool {
+        self.contains_fp(self.fingerprint(item))
+    }
+}
+
+impl QuotientFilter {
+    pub fn new(q: usize, r: usize) -> Self {
+        Self::with_seed(q, r, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed(q: usize, r: usize, seed: u64) -> Self {
+        // 2^q slots of r bit remainders. Filters can only be merged if they
+        // share the seed and q + r.
+        assert!(q > 0 && r > 0 && r <= 32 && q + r <= 64);
+        let m = 1 << q;
+        Self {
+            q,
+            r,
+            rem: IntVec::new(r, m),
+            occupied: BitVec::new(m),
+            continuation: BitVec::new(m),
+            shifted: BitVec::new(m),
+            n_items: 0,
+            seeds: seeds_from(seed, 4),
+            seed,
+            hasher: SeaHash,
+        }
+    }
+
+    pub fn with_fpr(fpr: f32, n: usize) -> Self {
+        /* Create QF with fp rate `fpr` and `n` expected elements: fpr ~ 2^-r */
+        let r = max((1.0 / fpr).log2().ceil() as usize, 1);
+        let q = max(clog((n as f32 / MAX_LOAD).ceil() as usize + 1), 1);
+        Self::new(q, r)
+    }
+}
+
+impl<F: FilterHasher> QuotientFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> QuotientFilter<G> {
+        // Switch the hash function; only valid before inserting
+        QuotientFilter {
+            q: self.q,
+            r: self.r,
+            rem: self.rem,
+            occupied: self.occupied,
+            continuation: self.continuation,
+            shifted: self.shifted,
+            n_items: self.n_items,
+            seeds: self.seeds,
+            seed: self.seed,
+            hasher,
+        }
+    }
+
+    pub fn try_insert<H: Hash>(&mut self, item: &H) -> Result<(), FilterError> {
+        if self.n_items + 1 > self.max_items() {
+            return Err(FilterError::Full)
+        }
+        self.insert_fp(self.fingerprint(item));
+        Ok(())
+    }
+
+    pub fn remove<H: Hash>(&mut self, item: &H) -> bool {
+        // Remove one copy of item's fingerprint
+        let (quot, rem) = self.split(self.fingerprint(item));
+        if !self.occupied.get(quot) { return false }
+        let start = self.block_start(quot);
+        let mut entries = self.decode(start);
+        let old_len = entries.len();
+        let key = (self.unwrap(quot, start), rem);
+        match entries.iter().position(|&e| e == key) {
+            Some(k) => {
+                entries.remove(k);
+                self.encode(start, old_len, &entries);
+                self.n_items -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn merge(&mut self, other: &QuotientFilter<F>) -> Result<(), FilterError> {
+        // Add every fingerprint of other (multiset union), growing self if
+        // needed. Both filters must use the same seed and fingerprint size.
+        if self.seed != other.seed || self.q + self.r != other.q + other.r {
+            return Err(FilterError::Incompatible)
+        }
+        let needed = self.n_items + other.n_items;
+        let mut capacity = self.capacity();
+        while Self::max_items_for(capacity) < needed {
+            capacity *= 2;
+        }
+        if capacity != self.capacity() {
+            self.resize(capacity)?;
+        }
+        for fp in other.fingerprints() {
+            self.insert_fp(fp);
+        }
+        Ok(())
+    }
+
+    pub fn resize(&mut self, new_capacity: usize) -> Result<(), FilterError> {
+        // Rebuild with 2^ceil(log2(new_capacity)) slots, keeping fingerprints:
+        // each doubling moves one remainder bit into the quotient, so the
+        // false positive rate grows as the table grows.
+        let p = self.q + self.r;
+        let q = max(clog(max(new_capacity, 2)), 1);
+        if q >= p || q + 32 < p || Self::max_items_for(1 << q) < self.n_items {
+            return Err(FilterError::Full)
+        }
+        let mut qf = QuotientFilter::with_seed(q, p - q, self.seed).with_hasher(self.hasher.clone());
+        for fp in self.fingerprints() {
+            qf.insert_fp(fp);
+        }
+        *self = qf;
+        Ok(())
+    }
+
+    pub fn fingerprints(&self) -> impl Iterator<Item = u64> {
+        // Stored fingerprints in ascending order, with multiplicity
+        let mut fps = Vec::with_capacity(self.n_items);
+        if self.n_items > 0 {
+            let m = self.capacity();
+            let empty = (0..m).find(|&i| self.is_empty_slot(i)).unwrap();
+            let mut k = 1;
+            while k <= m {
+                let s = (empty + k) % m;
+                if self.is_empty_slot(s) {
+                    k += 1;
+                    continue
+                }
+                let entries = self.decode(s);
+                for &(uq, rem) in entries.iter() {
+                    fps.push((((uq % m) as u64) << self.r) | rem as u64);
+                }
+                k += entries.len();
+            }
+            fps.sort_unstable();
+        }
+        fps.into_iter()
+    }
+
+    fn fingerprint<H: Hash>(&self, item: &H) -> u64 {
+        let h = seeded_hash(&self.hasher, &self.seeds, 0, item);
+        let p = self.q + self.r;
+        if p == 64 { h } else { h & ((1 << p) - 1) }
+    }
+
+    fn split(&self, fp: u64) -> (usize, u32) {
+        ((fp >> self.r) as usize, (fp & ((1 << self.r) - 1)) as u32)
+    }
+
+    fn contains_fp(&self, fp: u64) -> bool {
+        let (quot, rem) = self.split(fp);
+        if !self.occupied.get(quot) { return false }
+        let start = self.block_start(quot);
+        let key = (self.unwrap(quot, start), rem);
+        self.decode(start).contains(&key)
+    }
+
+    fn insert_fp(&mut self, fp: u64) {
+        let (quot, rem) = self.split(fp);
+        if self.is_empty_slot(quot) {
+            self.encode(quot, 0, &[(quot, rem)]);
+        } else {
+            let start = self.block_start(quot);
+            let mut entries = self.decode(start);
+            let old_len = entries.len();
+            let key = (self.unwrap(quot, start), rem);
+            let k = entries.iter().position(|&e| e > key).unwrap_or(old_len);
+            entries.insert(k, key);
+            self.encode(start, old_len, &entries);
+        }
+        self.n_items += 1;
+    }
+
+    fn is_empty_slot(&self, i: usize) -> bool {
+        !self.occupied.get(i) && !self.continuation.get(i) && !self.shifted.get(i)
+    }
+
+    fn prev(&self, i: usize) -> usize {
+        if i == 0 { self.capacity() - 1 } else { i - 1 }
+    }
+
+    fn unwrap(&self, i: usize, start: usize) -> usize {
+        // Slot i as an offset from start, without wrapping
+        if i >= start { i } else { i + self.capacity() }
+    }
+
+    fn block_start(&self, i: usize) -> usize {
+        // First slot of the maximal block of non-empty slots holding i
+        let mut s = i;
+        while !self.is_empty_slot(self.prev(s)) {
+            s = self.prev(s);
+        }
+        s
+    }
+
+    fn decode(&self, start: usize) -> Vec<(usize, u32)> {
+        // (unwrapped quotient, remainder) of every slot in the block at start
+        let m = self.capacity();
+        let mut entries = vec![];
+        let mut quot = start;
+        let mut s = start;
+        while !self.is_empty_slot(s % m) {
+            if s != start && !self.continuation.get(s % m) {
+                // New run: next occupied quotient
+                quot += 1;
+                while !self.occupied.get(quot % m) {
+                    quot += 1;
+                }
+            }
+            entries.push((quot, self.rem.get_int(s % m)));
+            s += 1;
+        }
+        entries
+    }
+
+    fn encode(&mut self, start: usize, old_len: usize, entries: &[(usize, u32)]) {
+        // Replace the old_len slots at start with entries in canonical layout
+        let m = self.capacity();
+        for s in start..start + old_len {
+            self.rem.set_int(s % m, 0);
+            self.occupied.set(s % m, false);
+            self.continuation.set(s % m, false);
+            self.shifted.set(s % m, false);
+        }
+        let mut pos = start;
+        let mut prev = None;
+        for &(quot, rem) in entries.iter() {
+            pos = max(pos, quot);
+            let s = pos % m;
+            self.rem.set_int(s, rem);
+            self.continuation.set(s, prev == Some(quot));
+            self.shifted.set(s, pos != quot);
+            self.occupied.set(quot % m, true);
+            prev = Some(quot);
+            pos += 1;
+        }
+    }
+
+    fn max_items_for(capacity: usize) -> usize {
+        min((capacity as f32 * MAX_LOAD) as usize, capacity - 1)
+    }
+
+    fn max_items(&self) -> usize {
+        Self::max_items_for(self.capacity())
+    }
+
+    pub fn len(&self) -> usize {
+        // Number of stored fingerprints
+        self.n_items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_items == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        1 << self.q
+    }
+
+    pub fn quotient_bits(&self) -> usize {
+        self.q
+    }
+
+    pub fn remainder_bits(&self) -> usize {
+        self.r
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn load_factor(&self) -> f32 {
+        self.n_items as f32 / self.capacity() as f32
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for QuotientFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("QuotientFilter", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", self.rem.space().inline().bytes),
+            SpaceBreakdown::leaf("seeds", std::mem::size_of_val::<[u64]>(&self.seeds)),
+            SpaceBreakdown::node("metadata", vec![
+                self.occupied.space().inline().named("occupied"),
+                self.continuation.space().inline().named("continuation"),
+                self.shifted.space().inline().named("shifted"),
+            ]),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod quotient_filter_tests {
+    use crate::quotient_filter::*;
+    use crate::hashing::MultiplyShift;
+    use rand::Rng;
+
+    fn model_fps(qf: &QuotientFilter, items: &[u64]) -> Vec<u64> {
+        let mut fps: Vec<u64> = items.iter().map(|x| qf.fingerprint(x)).collect();
+        fps.sort_unstable();
+        fps
+    }
+
+    #[test]
+    fn insert_query_remove() {
+        let mut qf = QuotientFilter::with_fpr(0.01, 1000);
+        assert_eq!(qf.remainder_bits(), 7);
+        let items: Vec<u64> = (0..1000).collect();
+        for x in items.iter() {
+            qf.insert(x);
+        }
+        assert_eq!(qf.len(), 1000);
+        for x in items.iter() {
+            assert!(qf.query(x));
+        }
+        assert_eq!(qf.fingerprints().collect::<Vec<u64>>(), model_fps(&qf, &items));
+        let fp = (1000..11000_u64).filter(|x| qf.query(x)).count();
+        assert!(fp < 200, "{} false positives", fp);
+
+        for x in 0..500_u64 {
+            assert!(qf.remove(&x));
+        }
+        for x in 500..1000_u64 {
+            assert!(qf.query(&x));
+        }
+        assert_eq!(qf.fingerprints().collect::<Vec<u64>>(), model_fps(&qf, &items[500..]));
+    }
+
+    #[test]
+    fn dense_small_table() {
+        // Nearly full 64 slot table: long clusters that wrap around
+        let mut rng = rand::thread_rng();
+        let mut qf = QuotientFilter::new(6, 8);
+        let mut items: Vec<u64> = vec![];
+        for _ in 0..200 {
+            if items.len() < 60 && rng.gen_range(0, 3) > 0 {
+                let x = rng.gen::<u64>();
+                qf.try_insert(&x).unwrap();
+                items.push(x);
+            } else if !items.is_empty() {
+                let x = items.swap_remove(rng.gen_range(0, items.len()));
+                assert!(qf.remove(&x));
+            }
+            assert_eq!(qf.fingerprints().collect::<Vec<u64>>(), model_fps(&qf, &items));
+        }
+        while qf.len() < 60 {
+            let x = rng.gen::<u64>();
+            qf.try_insert(&x).unwrap();
+        }
+        assert_eq!(qf.try_insert(&0), Err(FilterError::Full));
+    }
+
+    #[test]
+    fn hasher() {
+        let mut a = QuotientFilter::with_seed(6, 10, 2).with_hasher(MultiplyShift);
+        let mut b = QuotientFilter::with_seed(6, 10, 2).with_hasher(MultiplyShift);
+        for x in 0..100_u64 {
+            a.insert(&x);
+            b.insert(&(x + 100));
+        }
+        a.merge(&b).unwrap();
+        assert!((0..200_u64).all(|x| a.query(&x)));
+        let encoded = bincode::serialize(&a).unwrap();
+        assert!(bincode::deserialize::<QuotientFilter>(&encoded).is_err());
+    }
+
+    #[test]
+    fn resize_and_merge() {
+        let mut a = QuotientFilter::with_seed(4, 12, 7);
+        let mut b = QuotientFilter::with_seed(4, 12, 7);
+        for x in 0..100_u64 {
+            a.insert(&x); // grows past 16 slots
+        }
+        assert!(a.capacity() >= 128);
+        assert_eq!(a.quotient_bits() + a.remainder_bits(), 16);
+        for x in 100..150_u64 {
+            b.insert(&x);
+        }
+        a.merge(&b).unwrap();
+        assert_eq!(a.len(), 150);
+        for x in 0..150_u64 {
+            assert!(a.query(&x));
+        }
+
+        a.resize(1024).unwrap();
+        assert_eq!(a.capacity(), 1024);
+        for x in 0..150_u64 {
+            assert!(a.query(&x));
+        }
+        assert_eq!(a.resize(16), Err(FilterError::Full));
+        assert_eq!(a.resize(1 << 16), Err(FilterError::Full));
+
+        // Different seeds or fingerprint sizes cannot be merged
+        let c = QuotientFilter::with_seed(4, 12, 8);
+        assert_eq!(a.merge(&c), Err(FilterError::Incompatible));
+        let d = QuotientFilter::with_seed(4, 8, 7);
+        assert_eq!(a.merge(&d), Err(FilterError::Incompatible));
+        assert_eq!(a.len(), 150);
+    }
+}
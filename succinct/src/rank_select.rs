@@ -1,6 +1,7 @@
 use super::bv::{IntVec, BitVec};
 use std::cmp::{min, max};
 use super::math::{cdiv, cdiv_2, clog};
+use super::space::{SpaceBreakdown, SpaceUsage};
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -132,11 +133,7 @@ impl RankSupport {
 
     pub fn size_of(&self) -> usize{
         // Size in bytes
-        let mut size = std::mem::size_of::<Self>();
-        size += self.bv.size_of();
-        size += self.rs.size_of();
-        size += self.rb.size_of();
-        size
+        self.space().bytes
     }
 
     
@@ -192,13 +189,34 @@ impl RankSupport {
     }
 
     pub fn overhead(&self) -> usize{
-        // Size in bits
-        self.size_of() * 8
+        // Size in bits beyond the bit vector payload
+        let space = self.space();
+        space.bits() - space.find("payload").unwrap().bits()
     }
 
     pub fn get(&self, i: usize) -> bool {
         self.bv.get(i)    
     }
+
+    pub fn bits(&self) -> &BitVec {
+        &self.bv
+    }
+}
+
+impl SpaceUsage for RankSupport {
+    fn space(&self) -> SpaceBreakdown {
+        // Select is a binary search over the rank directory, so it keeps no
+        // samples of its own
+        SpaceBreakdown::node("RankSupport", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", self.bv.space().inline().bytes),
+            SpaceBreakdown::node("rank directory", vec![
+                self.rs.space().inline().named("superblocks"),
+                self.rb.space().inline().named("blocks"),
+            ]),
+            SpaceBreakdown::leaf("select samples", 0),
+        ])
+    }
 }
 
 #[cfg(test)]
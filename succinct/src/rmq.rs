@@ -0,0 +1,515 @@
+use super::bv::{BitVec, IntVec};
+use super::rank_select::RankSupport;
+use super::math::{cdiv, clog, flog};
+use serde::{Serialize, Deserialize};
+use std::cmp::{max, min};
+
+// For every byte (first bit most significant, 1 = +1, 0 = -1): the minimum
+// prefix excess after 1..=8 bits, the offset of its rightmost occurrence,
+// and the total excess.
+static BYTE_MIN: [(i8, u8, i8); 256] = byte_min_table();
+
+const fn byte_min_table() -> [(i8, u8, i8); 256] {
+    let mut table = [(0_i8, 0_u8, 0_i8); 256];
+    let mut v = 0;
+    while v < 256 {
+        let mut e = 0_i8;
+        let mut min_e = i8::MAX;
+        let mut pos = 0_u8;
+        let mut t = 0;
+        while t < 8 {
+            if (v >> (7 - t)) & 1 == 1 { e += 1 } else { e -= 1 }
+            if e <= min_e {
+                min_e = e;
+                pos = t as u8;
+            }
+            t += 1;
+        }
+        table[v] = (min_e, pos, e);
+        v += 1;
+    }
+    table
+}
+
+// Bits per block; a block's minimum is eight BYTE_MIN lookups
+const BLOCK: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rmq {
+    // Range minimum queries in 2n + o(n) bits and O(1) time, without access
+    // to the array.
+    //
+    // The array is encoded as balanced parentheses: scanning left to right
+    // with a stack, every element popped writes ')' and every element pushed
+    // writes '('. The leftmost minimum of A[i..=j] is found from the
+    // rightmost minimum excess between the '(' of i and the '(' of j.
+    // A virtual root wraps the sequence so it is never empty.
+    //
+    // The '(' of i and j come from `sel`. The minimum of a 64-bit block is
+    // computed on the fly from one rank and BYTE_MIN, so no per-block values
+    // are stored. Blocks are grouped into superblocks of sb ~ log^2 n log log
+    // n bits, and a sparse table over the blocks of each superblock and one
+    // over superblocks keep only the offset of the winner in their window
+    // (k bits at level k). A range of blocks is then at most three bottom
+    // lookups and one top lookup.
+    n: usize,
+    bp: RankSupport,
+    sel: Select1,
+    sb: usize,           // blocks per superblock
+    bottom: Vec<IntVec>, // bottom[k-1][x]: offset of the block of the rightmost min in [x, x + 2^k), clipped to x's superblock
+    top: Vec<IntVec>,    // top[k-1][x]: offset of the superblock of the rightmost min in [x, x + 2^k)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Select1 {
+    // select1 with a constant number of lookups (after Clark). The ones are
+    // cut into superblocks of l1 = 2 log^2 n ones whose first position is
+    // sampled. A superblock spanning at least 4 l1 log n bits lists all its
+    // ones; the others keep the first one of every block of l2 = 2 log n
+    // ones, relative to the superblock, in O(log log n) bits. A block
+    // spanning at least l1 bits lists its ones too, so the rest is a
+    // popcount scan of fewer than l1 bits. The samples and listed ones take
+    // O(n log log n / log n) bits.
+    l1: usize,
+    l2: usize,
+    super_pos: IntVec,
+    super_sparse: BitVec,
+    super_list: IntVec,   // start in `listed` of a sparse superblock
+    listed: IntVec,
+    block_rel: IntVec,    // first one of a block, relative to its superblock
+    block_sparse: RankSupport,
+    block_listed: IntVec, // ones of sparse blocks, relative to their first one
+}
+
+impl Rmq {
+    pub fn new(a: &IntVec) -> Self {
+        Self::build(a.len(), |i| a.get_int(i))
+    }
+
+    pub fn from_slice(a: &[u32]) -> Self {
+        Self::build(a.len(), |i| a[i])
+    }
+
+    pub(crate) fn build<F: Fn(usize) -> u32>(n: usize, a: F) -> Self {
+        let m = 2 * n + 2;
+        let mut bv = BitVec::new(m);
+        let mut stack: Vec<u32> = vec![];
+        let mut p = 0;
+        bv.set(p, true);
+        p += 1;
+        for i in 0..n {
+            let v = a(i);
+            while stack.last().is_some_and(|&top| top > v) {
+                stack.pop();
+                p += 1;
+            }
+            stack.push(v);
+            bv.set(p, true);
+            p += 1;
+        }
+        assert_eq!(p + stack.len() + 1, m);
+
+        // Superblocks of ~log^2 n log log n bits keep the top table at
+        // o(n) bits
+        let lg = clog(m + 1);
+        let sb = max(cdiv(lg * lg * clog(lg), BLOCK), 1);
+        let mut rmq = Self {
+            n,
+            sel: Select1::new(&bv),
+            bp: RankSupport::new(bv),
+            sb,
+            bottom: vec![],
+            top: vec![],
+        };
+
+        let nb = cdiv(m, BLOCK);
+        let mins: Vec<usize> = (0..nb).map(|k| rmq.block_min(k).1).collect();
+        for k in 1..flog(sb) + 1 {
+            let half = 1 << (k - 1);
+            let mut level = IntVec::new(k, nb);
+            for x in 0..nb {
+                let prev = |y: usize| if k == 1 { 0 } else { rmq.bottom[k - 2].get_int(y) as usize };
+                let end = min((x / sb + 1) * sb, nb);
+                let off = if x + half >= end {
+                    prev(x)
+                } else {
+                    let l = x + prev(x);
+                    let r = x + half + prev(x + half);
+                    if mins[l] < mins[r] { l - x } else { r - x }
+                };
+                level.set_int(x, off as u32);
+            }
+            rmq.bottom.push(level);
+        }
+
+        let ns = cdiv(nb, sb);
+        let super_mins: Vec<usize> = (0..ns).map(|s| rmq.super_min(s).1).collect();
+        for k in 1..flog(ns) + 1 {
+            let half = 1 << (k - 1);
+            let len = ns - (1 << k) + 1;
+            let mut level = IntVec::new(k, len);
+            for x in 0..len {
+                let prev = |y: usize| if k == 1 { 0 } else { rmq.top[k - 2].get_int(y) as usize };
+                let l = x + prev(x);
+                let r = x + half + prev(x + half);
+                let off = if super_mins[l] < super_mins[r] { l - x } else { r - x };
+                level.set_int(x, off as u32);
+            }
+            rmq.top.push(level);
+        }
+        rmq
+    }
+
+    pub fn rmq(&self, i: usize, j: usize) -> usize {
+        // Position of the leftmost minimum in A[i..=j]
+        assert!(i <= j && j < self.n);
+        if i == j { return i }
+        let oi = self.sel.select1(self.bp.bits(), i + 2);
+        let oj = self.sel.select1(self.bp.bits(), j + 2);
+        let (p, e) = self.min_excess(oi, oj);
+        if e < self.excess(oi) {
+            self.bp.rank1(p + 1) - 2
+        } else {
+            i
+        }
+    }
+
+    fn excess(&self, p: usize) -> usize {
+        2 * self.bp.rank1(p) - (p + 1)
+    }
+
+    fn min_excess(&self, x: usize, y: usize) -> (usize, usize) {
+        // Rightmost position of the minimum excess in [x, y] and its excess
+        let bx = x / BLOCK;
+        let by = y / BLOCK;
+        if by <= bx + 1 {
+            return self.scan(x, y, self.excess(x), (x, self.excess(x)));
+        }
+
+        let mut best = self.scan(x, (bx + 1) * BLOCK - 1, self.excess(x), (x, self.excess(x)));
+
+        let blk = self.block_range_min(bx + 1, by - 1);
+        if blk.1 <= best.1 {
+            best = blk;
+        }
+
+        let start = by * BLOCK;
+        self.scan(start, y, self.excess(start), best)
+    }
+
+    fn scan(&self, x: usize, y: usize, ex: usize, best: (usize, usize)) -> (usize, usize) {
+        // Scan bits (x, y] starting from excess ex at x, whole bytes through
+        // BYTE_MIN and the remainder bit by bit
+        let mut best = if ex <= best.1 { (x, ex) } else { best };
+        let mut e = ex as isize;
+        let mut q = x + 1;
+        while q + 8 <= y + 1 {
+            let (min_e, pos, total) = BYTE_MIN[self.bp.bits().get_int(q, 8) as usize];
+            if e + min_e as isize <= best.1 as isize {
+                best = (q + pos as usize, (e + min_e as isize) as usize);
+            }
+            e += total as isize;
+            q += 8;
+        }
+        for q in q..y + 1 {
+            if self.bp.get(q) { e += 1 } else { e -= 1 }
+            if e <= best.1 as isize {
+                best = (q, e as usize);
+            }
+        }
+        best
+    }
+
+    fn block_min(&self, k: usize) -> (usize, usize) {
+        // Rightmost minimum excess in block k
+        let start = k * BLOCK;
+        let end = min(start + BLOCK, self.bp.len()) - 1;
+        let e = self.excess(start);
+        self.scan(start, end, e, (start, e))
+    }
+
+    fn in_super(&self, l: usize, r: usize) -> (usize, usize) {
+        // Rightmost minimum excess in blocks [l, r] of one superblock
+        if l == r { return self.block_min(l) }
+        let k = flog(r - l + 1);
+        let level = &self.bottom[k - 1];
+        let a = self.block_min(l + level.get_int(l) as usize);
+        let x = r + 1 - (1 << k);
+        let c = self.block_min(x + level.get_int(x) as usize);
+        if a.1 < c.1 { a } else { c }
+    }
+
+    fn super_min(&self, s: usize) -> (usize, usize) {
+        let nb = cdiv(self.bp.len(), BLOCK);
+        self.in_super(s * self.sb, min((s + 1) * self.sb, nb) - 1)
+    }
+
+    fn block_range_min(&self, l: usize, r: usize) -> (usize, usize) {
+        // Rightmost minimum excess in blocks [l, r]
+        let (sl, sr) = (l / self.sb, r / self.sb);
+        if sl == sr { return self.in_super(l, r) }
+        let mut best = self.in_super(l, (sl + 1) * self.sb - 1);
+
+        if sl + 1 < sr {
+            let (x, y) = (sl + 1, sr - 1);
+            let s = if x == y {
+                x
+            } else {
+                let k = flog(y - x + 1);
+                let level = &self.top[k - 1];
+                let a = x + level.get_int(x) as usize;
+                let z = y + 1 - (1 << k);
+                let c = z + level.get_int(z) as usize;
+                if self.super_min(a).1 < self.super_min(c).1 { a } else { c }
+            };
+            let m = self.super_min(s);
+            if m.1 <= best.1 {
+                best = m;
+            }
+        }
+
+        let m = self.in_super(sr * self.sb, r);
+        if m.1 <= best.1 {
+            best = m;
+        }
+        best
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.bp.size_of();
+        size += self.sel.size_of();
+        size += self.bottom.iter().chain(self.top.iter()).map(|t| t.size_of()).sum::<usize>();
+        size
+    }
+}
+
+impl Select1 {
+    fn new(bv: &BitVec) -> Self {
+        let m = bv.len();
+        let ones: Vec<usize> = (0..m).filter(|&p| bv.get(p)).collect();
+        assert!(!ones.is_empty());
+        let lg = max(clog(m + 1), 4);
+        let (l2, l1) = (2 * lg, 2 * lg * lg);
+        let t = l1; // span of a sparse block
+        let far = 4 * l1 * lg; // span of a sparse superblock
+        let n_super = cdiv(ones.len(), l1);
+        let n_blocks = cdiv(ones.len(), l2);
+
+        let w = max(clog(m + 1), 1);
+        let wr = clog(far);
+        let mut super_pos = IntVec::new(w, n_super);
+        let mut super_sparse = BitVec::new(n_super);
+        let mut super_list = IntVec::new(max(clog(ones.len() + 1), 1), n_super);
+        let mut listed: Vec<u32> = vec![];
+        let mut block_rel = IntVec::new(wr, n_blocks);
+        let mut block_sparse = BitVec::new(n_blocks);
+        let mut block_listed: Vec<u32> = vec![];
+        for (q, group) in ones.chunks(l1).enumerate() {
+            let first = group[0];
+            super_pos.set_int(q, first as u32);
+            if group[group.len() - 1] + 1 - first >= far {
+                super_sparse.set(q, true);
+                super_list.set_int(q, listed.len() as u32);
+                listed.extend(group.iter().map(|&p| p as u32));
+                continue;
+            }
+            for (k, block) in group.chunks(l2).enumerate() {
+                let b = q * (l1 / l2) + k;
+                block_rel.set_int(b, (block[0] - first) as u32);
+                if block[block.len() - 1] + 1 - block[0] >= t {
+                    block_sparse.set(b, true);
+                    block_listed.extend(block.iter().map(|&p| (p - block[0]) as u32));
+                }
+            }
+        }
+
+        Self {
+            l1,
+            l2,
+            super_pos,
+            super_sparse,
+            super_list,
+            listed: IntVec::from_vec(&listed, w),
+            block_rel,
+            block_sparse: RankSupport::new(block_sparse),
+            block_listed: IntVec::from_vec(&block_listed, wr),
+        }
+    }
+
+    fn select1(&self, bits: &BitVec, r: usize) -> usize {
+        // Position of the r-th (1-based) one, which must exist
+        let x = r - 1;
+        let q = x / self.l1;
+        if self.super_sparse.get(q) {
+            return self.listed.get_int(self.super_list.get_int(q) as usize + x % self.l1) as usize;
+        }
+        let b = x / self.l2;
+        let base = self.super_pos.get_int(q) as usize + self.block_rel.get_int(b) as usize;
+        let mut k = x % self.l2;
+        if k == 0 { return base }
+        if self.block_sparse.get(b) {
+            let start = (self.block_sparse.rank1(b) - 1) * self.l2;
+            return base + self.block_listed.get_int(start + k) as usize;
+        }
+
+        // Dense block: the k-th one after base is less than t bits away
+        let mut p = base + 1;
+        loop {
+            let w = min(32, bits.len() - p);
+            let word = bits.get_int(p, w);
+            let c = word.count_ones() as usize;
+            if k <= c {
+                // Clear the k - 1 highest ones of the word
+                let mut word = word << (32 - w);
+                for _ in 1..k {
+                    word &= !(0x8000_0000 >> word.leading_zeros());
+                }
+                return p + word.leading_zeros() as usize;
+            }
+            k -= c;
+            p += w;
+        }
+    }
+
+    fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.super_pos.size_of();
+        size += self.super_sparse.size_of();
+        size += self.super_list.size_of();
+        size += self.listed.size_of();
+        size += self.block_rel.size_of();
+        size += self.block_sparse.size_of();
+        size += self.block_listed.size_of();
+        size
+    }
+}
+
+#[cfg(test)]
+mod rmq_tests {
+    use crate::rmq::*;
+    use rand::Rng;
+
+    fn naive(a: &[u32], i: usize, j: usize) -> usize {
+        let mut best = i;
+        for k in i..j + 1 {
+            if a[k] < a[best] {
+                best = k;
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn small() {
+        let a = [5, 3, 4, 1, 2, 1, 6];
+        let rmq = Rmq::from_slice(&a);
+        assert_eq!(rmq.rmq(0, 6), 3);
+        assert_eq!(rmq.rmq(0, 2), 1);
+        assert_eq!(rmq.rmq(4, 6), 5);
+        assert_eq!(rmq.rmq(2, 2), 2);
+        for i in 0..a.len() {
+            for j in i..a.len() {
+                assert_eq!(rmq.rmq(i, j), naive(&a, i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn intvec() {
+        let a = vec![0, 1, 3, 0, 0, 2];
+        let rmq = Rmq::new(&IntVec::from_vec(&a, 2));
+        assert_eq!(rmq.len(), 6);
+        assert_eq!(rmq.rmq(1, 2), 1);
+        assert_eq!(rmq.rmq(1, 5), 3);
+        assert_eq!(rmq.rmq(4, 5), 4);
+    }
+
+    #[test]
+    fn fuzz() {
+        let mut rng = rand::thread_rng();
+        for &(n, max_v) in &[(1, 1), (50, 3), (700, 10), (3000, 1000)] {
+            let a: Vec<u32> = (0..n).map(|_| rng.gen_range(0, max_v)).collect();
+            let rmq = Rmq::from_slice(&a);
+            for _ in 0..2000 {
+                let i = rng.gen_range(0, n);
+                let j = rng.gen_range(i, n);
+                assert_eq!(rmq.rmq(i, j), naive(&a, i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn byte_table() {
+        assert_eq!(BYTE_MIN[0xff], (1, 0, 8));
+        assert_eq!(BYTE_MIN[0x00], (-8, 7, -8));
+        assert_eq!(BYTE_MIN[0b1010_1010], (0, 7, 0));
+        assert_eq!(BYTE_MIN[0b0110_0011], (-2, 5, 0));
+    }
+
+    #[test]
+    fn select() {
+        // Dense runs, gaps past log^2 n (sparse blocks) and a stretch of
+        // ones 200 bits apart (a sparse superblock)
+        let mut rng = rand::thread_rng();
+        let mut bv = BitVec::new(300_000);
+        let mut p = 0;
+        while p < 100_000 {
+            bv.set(p, rng.gen::<bool>());
+            p += if rng.gen_range(0, 50) == 0 { 1000 } else { 1 };
+        }
+        while p < 280_000 {
+            bv.set(p, true);
+            p += 200;
+        }
+        for q in 280_000..300_000 {
+            bv.set(q, rng.gen::<bool>());
+        }
+        let sel = Select1::new(&bv);
+        assert!(sel.listed.len() > 0);
+        assert!(sel.block_listed.len() > 0);
+        let ones: Vec<usize> = (0..bv.len()).filter(|&q| bv.get(q)).collect();
+        for (r, &q) in ones.iter().enumerate() {
+            assert_eq!(sel.select1(&bv, r + 1), q);
+        }
+    }
+
+    #[test]
+    fn large() {
+        // Enough blocks for several superblocks and top table levels
+        let mut rng = rand::thread_rng();
+        let n = 60_000;
+        let a: Vec<u32> = (0..n).map(|i| if i % 5000 == 0 { 0 } else { rng.gen_range(1, 1 << 20) }).collect();
+        let rmq = Rmq::from_slice(&a);
+        assert!(rmq.top.len() > 2);
+        for _ in 0..500 {
+            let i = rng.gen_range(0, n);
+            let j = rng.gen_range(i, n);
+            assert_eq!(rmq.rmq(i, j), naive(&a, i, j));
+        }
+
+        // Select and the sparse tables stay below the 2n + 2 bits of
+        // parentheses
+        assert!((rmq.size_of() - rmq.bp.size_of()) * 8 < 2 * n);
+    }
+
+    #[test]
+    fn sorted() {
+        let a: Vec<u32> = (0..500).collect();
+        let rmq = Rmq::from_slice(&a);
+        assert_eq!(rmq.rmq(10, 400), 10);
+        let a: Vec<u32> = (0..500).rev().collect();
+        let rmq = Rmq::from_slice(&a);
+        assert_eq!(rmq.rmq(10, 400), 400);
+    }
+}
@@ -0,0 +1,222 @@
+use super::bloom_filter::{bf_with_fpr_config, BloomFilter, Query, MQ};
+use super::hashing::{seeds_from, FilterHasher, HashScheme, SeaHash};
+use super::space::{SpaceBreakdown, SpaceUsage};
+
+use rand::Rng;
+use std::cmp::max;
+use std::hash::Hash;
+use serde::{Serialize, Deserialize};
+
+// Each stage holds GROWTH times the items of the previous one
+const GROWTH: usize = 2;
+// and has TIGHTENING times its fp rate
+const TIGHTENING: f32 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScalableBloomFilter<F = SeaHash> {
+    // Scalable Bloom filter (Almeida et al.). Items go into the last stage
+    // until it holds its capacity, then a larger stage is added. Stage i
+    // is sized for fp rate fpr (1 - r) r^i, so the overall fp rate stays
+    // below sum_i fpr (1 - r) r^i < fpr however many stages are added.
+    stages: Vec<BloomFilter<F>>,
+    counts: Vec<usize>,
+    capacities: Vec<usize>,
+    fpr: f32,
+    seed: u64,
+    scheme: HashScheme,
+    hasher: F,
+}
+
+impl<F: FilterHasher> MQ for ScalableBloomFilter<F> {
+    fn insert<H: Hash>(&mut self, item: &H) {
+        // Items already (or falsely) reported present are not added again
+        if self.query(item) { return }
+        let last = self.stages.len() - 1;
+        if self.counts[last] >= self.capacities[last] {
+            self.add_stage();
+        }
+        let last = self.stages.len() - 1;
+        self.stages[last].insert(item);
+        self.counts[last] += 1;
+    }
+}
+
+impl<F: FilterHasher> Query for ScalableBloomFilter<F> {
+    fn query<H: Hash>(&self, item: &H) -> bool {
+        self.stages.iter().any(|bf| bf.query(item))
+    }
+}
+
+impl ScalableBloomFilter {
+    pub fn new(fpr: f32, initial_capacity: usize) -> Self {
+        /* Create SBF with overall fp rate `fpr`, first stage sized for
+           `initial_capacity` items */
+        Self::with_seed(fpr, initial_capacity, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed(fpr: f32, initial_capacity: usize, seed: u64) -> Self {
+        assert!(fpr > 0.0 && fpr < 1.0);
+        let mut sbf = Self {
+            stages: vec![],
+            counts: vec![],
+            capacities: vec![],
+            fpr,
+            seed,
+            scheme: HashScheme::default(),
+            hasher: SeaHash,
+        };
+        sbf.capacities.push(max(initial_capacity, 1));
+        sbf.add_stage();
+        sbf
+    }
+}
+
+impl<F: FilterHasher> ScalableBloomFilter<F> {
+    pub fn with_hasher<G: FilterHasher>(self, hasher: G) -> ScalableBloomFilter<G> {
+        // Switch the hash function; only valid before inserting
+        let mut sbf = ScalableBloomFilter {
+            stages: vec![],
+            counts: vec![],
+            capacities: vec![self.capacities[0]],
+            fpr: self.fpr,
+            seed: self.seed,
+            scheme: self.scheme,
+            hasher,
+        };
+        sbf.add_stage();
+        sbf
+    }
+
+    pub fn with_scheme(mut self, scheme: HashScheme) -> Self {
+        // Switch how probe hashes are derived; only valid before inserting
+        self.scheme = scheme;
+        self.stages.clear();
+        self.counts.clear();
+        self.capacities.truncate(1);
+        self.add_stage();
+        self
+    }
+
+    fn add_stage(&mut self) {
+        // Append stage i, sized for capacities[i] items (its capacity is
+        // already recorded for the first stage)
+        let i = self.stages.len();
+        if i > 0 {
+            self.capacities.push(self.capacities[i - 1] * GROWTH);
+        }
+        let (k, m) = bf_with_fpr_config(self.stage_fpr(i), self.capacities[i]);
+        let seed = seeds_from(self.seed, i + 1)[i];
+        let bf = BloomFilter::with_seed(k, m, seed).with_scheme(self.scheme)
+                                                    .with_hasher(self.hasher.clone());
+        self.stages.push(bf);
+        self.counts.push(0);
+    }
+
+    fn stage_fpr(&self, i: usize) -> f32 {
+        self.fpr * (1.0 - TIGHTENING) * TIGHTENING.powi(i as i32)
+    }
+
+    pub fn n_stages(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn len(&self) -> usize {
+        // Number of items added
+        self.counts.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        // Items the current stages hold before another is added
+        self.capacities.iter().sum()
+    }
+
+    pub fn fpr_bound(&self) -> f32 {
+        // Target fp rate, an upper bound for any number of stages
+        self.fpr
+    }
+
+    pub fn estimated_fpr(&self) -> f64 {
+        // 1 - prod_i (1 - f_i), with f_i = fill_ratio^k of stage i
+        let pass: f64 = self.stages.iter()
+                                   .map(|bf| 1.0 - bf.fill_ratio().powi(bf.n_hashes() as i32))
+                                   .product();
+        1.0 - pass
+    }
+
+    pub fn stages(&self) -> &[BloomFilter<F>] {
+        &self.stages
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn scheme(&self) -> HashScheme {
+        self.scheme
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for ScalableBloomFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        let stages = self.stages.iter().map(|bf| bf.space()).collect();
+        SpaceBreakdown::node("ScalableBloomFilter", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::node("stages", stages),
+            SpaceBreakdown::leaf("counts", std::mem::size_of_val::<[usize]>(&self.counts)),
+            SpaceBreakdown::leaf("capacities", std::mem::size_of_val::<[usize]>(&self.capacities)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod scalable_bloom_filter_tests {
+    use crate::scalable_bloom_filter::*;
+
+    #[test]
+    fn grows() {
+        let mut sbf = ScalableBloomFilter::with_seed(0.01, 100, 9);
+        assert_eq!(sbf.n_stages(), 1);
+        assert_eq!(sbf.estimated_fpr(), 0.0);
+        for i in 0..10000 {
+            sbf.insert(&i);
+        }
+        assert!(sbf.n_stages() >= 7);
+        assert!(sbf.len() <= 10000 && sbf.len() > 9900);
+        assert!(sbf.len() <= sbf.capacity());
+        for i in 0..10000 {
+            assert!(sbf.query(&i));
+        }
+        let fp = (10000..30000).filter(|i| sbf.query(i)).count();
+        assert!(fp < 200, "{} false positives", fp);
+        let est = sbf.estimated_fpr();
+        assert!(est > 0.0 && est < sbf.fpr_bound() as f64, "{}", est);
+
+        // Duplicates do not use capacity
+        let len = sbf.len();
+        sbf.insert(&5);
+        assert_eq!(sbf.len(), len);
+    }
+
+    #[test]
+    fn options() {
+        let mut sbf = ScalableBloomFilter::with_seed(0.05, 10, 1).with_scheme(HashScheme::DoubleHashing);
+        for i in 0..100 {
+            sbf.insert_bytes(&(i as u32).to_le_bytes());
+        }
+        assert!(sbf.n_stages() > 1);
+        assert!(sbf.stages().iter().all(|bf| bf.scheme() == HashScheme::DoubleHashing));
+        let encoded = bincode::serialize(&sbf).unwrap();
+        let sbf: ScalableBloomFilter = bincode::deserialize(&encoded).unwrap();
+        assert!(sbf.query_bytes(&7_u32.to_le_bytes()));
+        assert_eq!(sbf.space().find("stages").unwrap().children.len(), sbf.n_stages());
+    }
+}
@@ -0,0 +1,328 @@
+use super::bv::BitVec;
+use super::rank_select::RankSupport;
+use super::wt::WT;
+use serde::{Serialize, Deserialize};
+use std::io::{self, BufRead};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordMap {
+    // Record boundaries over the concatenated sequence. Record j of length
+    // len_j is written as 0^len_j 1, so empty records are representable and
+    // starts, lengths and the record of a position are rank/select queries.
+    names: Vec<String>,
+    by_name: Vec<usize>, // record ids sorted by name, for id_of
+    n: usize,
+    rs: RankSupport,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SeqIndex {
+    // Wavelet tree over the concatenated records, queried by (record, offset)
+    wt: WT,
+    records: RecordMap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeqFormat {
+    Fasta,
+    Fastq,
+    Raw, // bare sequence lines, read as one unnamed record
+}
+
+impl RecordMap {
+    pub fn new<S: AsRef<str>>(names: &[S], lens: &[usize]) -> Self {
+        assert_eq!(names.len(), lens.len());
+        assert!(!lens.is_empty(), "no records");
+        let n: usize = lens.iter().sum();
+        let mut bv = BitVec::new(n + lens.len());
+        let mut p = 0;
+        for &len in lens.iter() {
+            p += len;
+            bv.set(p, true);
+            p += 1;
+        }
+        let names: Vec<String> = names.iter().map(|s| s.as_ref().to_string()).collect();
+        // Stable sort, so duplicate names resolve to their first record
+        let mut by_name: Vec<usize> = (0..names.len()).collect();
+        by_name.sort_by(|&a, &b| names[a].cmp(&names[b]));
+        Self {
+            names,
+            by_name,
+            n,
+            rs: RankSupport::new(bv),
+        }
+    }
+
+    pub fn n_records(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn name(&self, record: usize) -> &str {
+        &self.names[record]
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<usize> {
+        // Binary search over the name-sorted record ids
+        let i = self.by_name.partition_point(|&r| self.names[r].as_str() < name);
+        self.by_name.get(i).copied().filter(|&r| self.names[r] == name)
+    }
+
+    pub fn start(&self, record: usize) -> usize {
+        // Global position of the first base of record
+        assert!(record < self.n_records());
+        if record == 0 { 0 } else { self.rs.select1(record).unwrap() + 1 - record }
+    }
+
+    pub fn record_len(&self, record: usize) -> usize {
+        let end = self.rs.select1(record + 1).unwrap() - record;
+        end - self.start(record)
+    }
+
+    pub fn to_global(&self, record: usize, offset: usize) -> usize {
+        assert!(offset < self.record_len(record));
+        self.start(record) + offset
+    }
+
+    pub fn to_local(&self, pos: usize) -> (usize, usize) {
+        // (record, offset) of a global position
+        assert!(pos < self.n);
+        let p = self.rs.select0(pos + 1).unwrap();
+        let record = self.rs.rank1(p);
+        (record, pos - self.start(record))
+    }
+
+    pub fn len(&self) -> usize {
+        // Total bases over all records
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.names.iter().map(|s| std::mem::size_of::<String>() + s.len()).sum::<usize>();
+        size += self.by_name.len() * std::mem::size_of::<usize>();
+        size += self.rs.size_of();
+        size
+    }
+}
+
+pub fn read_records<R: BufRead>(reader: R) -> io::Result<(String, RecordMap, SeqFormat)> {
+    // Parse FASTA, FASTQ or raw sequence lines into the concatenated
+    // sequence (headers, line breaks and qualities removed) and its records.
+    // The format is picked from the first non-empty line.
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut seq = String::new();
+    let mut names: Vec<String> = vec![];
+    let mut lens: Vec<usize> = vec![];
+    let mut format = None;
+    let mut lines = reader.lines();
+
+    while let Some(line) = lines.next() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() { continue }
+
+        let fmt = *format.get_or_insert(match line.as_bytes()[0] {
+            b'>' => SeqFormat::Fasta,
+            b'@' => SeqFormat::Fastq,
+            _ => SeqFormat::Raw,
+        });
+        match fmt {
+            SeqFormat::Raw => {
+                if lens.is_empty() {
+                    names.push(String::new());
+                    lens.push(0);
+                }
+                push_bases(&mut seq, line, &mut lens)?;
+            }
+            SeqFormat::Fasta => {
+                if let Some(header) = line.strip_prefix('>') {
+                    names.push(record_name(header));
+                    lens.push(0);
+                } else if lens.is_empty() {
+                    return Err(invalid("sequence before first FASTA header"));
+                } else {
+                    push_bases(&mut seq, line, &mut lens)?;
+                }
+            }
+            SeqFormat::Fastq => {
+                let header = line.strip_prefix('@').ok_or_else(|| invalid("expected FASTQ header"))?;
+                names.push(record_name(header));
+                lens.push(0);
+
+                // Sequence lines up to the '+' separator
+                loop {
+                    let l = lines.next().ok_or_else(|| invalid("truncated FASTQ record"))??;
+                    let l = l.trim_end();
+                    if l.starts_with('+') { break }
+                    push_bases(&mut seq, l, &mut lens)?;
+                }
+
+                // Quality lines until they cover the sequence
+                let len = *lens.last().unwrap();
+                let mut qual = 0;
+                while qual < len {
+                    let l = lines.next().ok_or_else(|| invalid("truncated FASTQ record"))??;
+                    qual += l.trim_end().len();
+                }
+                if qual != len {
+                    return Err(invalid("FASTQ quality length differs from sequence length"));
+                }
+            }
+        }
+    }
+
+    if lens.is_empty() {
+        return Err(invalid("no records"));
+    }
+    let records = RecordMap::new(&names, &lens);
+    Ok((seq, records, format.unwrap()))
+}
+
+fn record_name(header: &str) -> String {
+    // Record name is the header up to the first whitespace
+    header.split_whitespace().next().unwrap_or("").to_string()
+}
+
+fn push_bases(seq: &mut String, line: &str, lens: &mut [usize]) -> io::Result<()> {
+    if !line.is_ascii() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "sequence is not ascii"));
+    }
+    seq.push_str(line);
+    *lens.last_mut().unwrap() += line.len();
+    Ok(())
+}
+
+impl SeqIndex {
+    pub fn new(seq: &str, records: RecordMap) -> Self {
+        assert_eq!(seq.len(), records.len());
+        Self {
+            wt: WT::new(seq),
+            records,
+        }
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let (seq, records, _) = read_records(reader)?;
+        if seq.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no bases"));
+        }
+        Ok(Self::new(&seq, records))
+    }
+
+    pub fn records(&self) -> &RecordMap {
+        &self.records
+    }
+
+    pub fn wt(&self) -> &WT {
+        &self.wt
+    }
+
+    pub fn access(&self, record: usize, offset: usize) -> char {
+        self.wt.access(self.records.to_global(record, offset))
+    }
+
+    fn rank_before(&self, c: char, pos: usize) -> usize {
+        // Occurrences of c in [0, pos)
+        if pos == 0 { 0 } else { self.wt.rank(c, pos - 1) }
+    }
+
+    pub fn rank(&self, c: char, record: usize, offset: usize) -> usize {
+        // Occurrences of c in the record up to and including offset
+        if !self.wt.contains_symbol(c) { return 0 }
+        let start = self.records.start(record);
+        let pos = self.records.to_global(record, offset);
+        self.wt.rank(c, pos) - self.rank_before(c, start)
+    }
+
+    pub fn select(&self, c: char, record: usize, rank: usize) -> Option<usize> {
+        // Offset of the rank-th (1-based) c in the record
+        if rank == 0 || !self.wt.contains_symbol(c) { return None }
+        let start = self.records.start(record);
+        let end = start + self.records.record_len(record);
+        let pos = self.wt.select(c, self.rank_before(c, start) + rank)?;
+        if pos < end { Some(pos - start) } else { None }
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        std::mem::size_of::<Self>() + self.wt.size_of() + self.records.size_of()
+    }
+}
+
+#[cfg(test)]
+mod seq_tests {
+    use crate::seq::*;
+
+    const FASTA: &str = ">chr1 first\nACGT\nAC\n\n>empty\n>chr2\nGGTA\n";
+    const FASTQ: &str = "@r1 x\nACGTT\n+\nIIIII\n@r2\nGA\n+r2\n#I\n";
+
+    #[test]
+    fn fasta() {
+        let (seq, rec, fmt) = read_records(FASTA.as_bytes()).unwrap();
+        assert_eq!(fmt, SeqFormat::Fasta);
+        assert_eq!(seq, "ACGTACGGTA");
+        assert_eq!(rec.n_records(), 3);
+        assert_eq!(rec.name(0), "chr1");
+        assert_eq!(rec.id_of("chr2"), Some(2));
+        assert_eq!(rec.id_of("empty"), Some(1));
+        assert_eq!(rec.id_of("chr3"), None);
+        assert_eq!(rec.id_of(""), None);
+        assert_eq!(rec.record_len(0), 6);
+        assert_eq!(rec.record_len(1), 0);
+        assert_eq!(rec.start(2), 6);
+        assert_eq!(rec.to_local(7), (2, 1));
+        assert_eq!(rec.to_global(2, 1), 7);
+        for pos in 0..rec.len() {
+            let (r, o) = rec.to_local(pos);
+            assert_eq!(rec.to_global(r, o), pos);
+        }
+    }
+
+    #[test]
+    fn fastq_and_raw() {
+        let (seq, rec, fmt) = read_records(FASTQ.as_bytes()).unwrap();
+        assert_eq!(fmt, SeqFormat::Fastq);
+        assert_eq!(seq, "ACGTTGA");
+        assert_eq!(rec.name(1), "r2");
+        assert_eq!(rec.to_local(5), (1, 0));
+
+        let (seq, rec, fmt) = read_records("ACG\nTT\n".as_bytes()).unwrap();
+        assert_eq!(fmt, SeqFormat::Raw);
+        assert_eq!(seq, "ACGTT");
+        assert_eq!(rec.n_records(), 1);
+
+        assert!(read_records("@r1\nACG\n+\nII\n".as_bytes()).is_err());
+        assert!(read_records("".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn queries() {
+        let idx = SeqIndex::from_reader(FASTA.as_bytes()).unwrap();
+        assert_eq!(idx.access(0, 4), 'A');
+        assert_eq!(idx.access(2, 0), 'G');
+        assert_eq!(idx.rank('A', 0, 5), 2);
+        assert_eq!(idx.rank('A', 2, 3), 1);
+        assert_eq!(idx.rank('G', 2, 1), 2);
+        assert_eq!(idx.select('A', 2, 1), Some(3));
+        assert_eq!(idx.select('G', 0, 1), Some(2));
+        assert_eq!(idx.select('G', 0, 2), None);
+        assert_eq!(idx.select('C', 1, 1), None);
+        assert_eq!(idx.rank('N', 0, 5), 0);
+        assert_eq!(idx.rank('a', 2, 3), 0);
+        assert_eq!(idx.select('N', 0, 1), None);
+    }
+
+    #[test]
+    fn duplicate_names() {
+        let rec = RecordMap::new(&["b", "a", "b", "c"], &[1, 2, 3, 4]);
+        assert_eq!(rec.id_of("a"), Some(1));
+        assert_eq!(rec.id_of("b"), Some(0));
+        assert_eq!(rec.id_of("c"), Some(3));
+        assert_eq!(rec.id_of("d"), None);
+    }
+}
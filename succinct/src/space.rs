@@ -0,0 +1,141 @@
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpaceBreakdown {
+    // Hierarchical memory report. `bytes` is the total of the subtree; a
+    // leaf is a single component (struct header, payload, directory, ...).
+    pub name: String,
+    pub bytes: usize,
+    pub children: Vec<SpaceBreakdown>,
+}
+
+pub trait SpaceUsage {
+    // Memory used by a structure, broken down by component. The struct
+    // header (`std::mem::size_of::<Self>()`) covers every field stored
+    // inline, so embedded structures only report their heap parts.
+    fn space(&self) -> SpaceBreakdown;
+
+    fn space_bytes(&self) -> usize {
+        self.space().bytes
+    }
+}
+
+impl SpaceBreakdown {
+    pub fn leaf(name: &str, bytes: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            bytes,
+            children: vec![],
+        }
+    }
+
+    pub fn node(name: &str, children: Vec<SpaceBreakdown>) -> Self {
+        Self {
+            name: name.to_string(),
+            bytes: children.iter().map(|c| c.bytes).sum(),
+            children,
+        }
+    }
+
+    pub fn header<T>() -> Self {
+        Self::leaf("header", std::mem::size_of::<T>())
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn inline(mut self) -> Self {
+        // Drop the header of a structure stored inside another one; it is
+        // already counted by the enclosing struct's header
+        self.children.retain(|c| c.name != "header");
+        self.bytes = self.children.iter().map(|c| c.bytes).sum();
+        self
+    }
+
+    pub fn bits(&self) -> usize {
+        self.bytes * 8
+    }
+
+    pub fn bits_per_symbol(&self, n: usize) -> f64 {
+        // Total bits divided by the number of represented symbols
+        self.bits() as f64 / n as f64
+    }
+
+    pub fn find(&self, name: &str) -> Option<&SpaceBreakdown> {
+        // First component called name, depth first
+        if self.name == name { return Some(self) }
+        self.children.iter().find_map(|c| c.find(name))
+    }
+
+    fn write_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize, total: usize) -> fmt::Result {
+        let pct = if total == 0 { 0.0 } else { 100.0 * self.bytes as f64 / total as f64 };
+        writeln!(f, "{:indent$}{}\t{} bytes\t{:.1}%", "", self.name, self.bytes, pct, indent = 2 * depth)?;
+        for c in self.children.iter() {
+            c.write_tree(f, depth + 1, total)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SpaceBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tree(f, 0, self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod space_tests {
+    use crate::space::*;
+    use crate::bv::{BitVec, IntVec};
+    use crate::rank_select::RankSupport;
+    use crate::wt::WT;
+    use crate::bloom_filter::{BloomFilter, BlockedBloomFilter};
+
+    #[test]
+    fn breakdown() {
+        let s = SpaceBreakdown::node("x", vec![
+            SpaceBreakdown::leaf("header", 8),
+            SpaceBreakdown::node("y", vec![SpaceBreakdown::leaf("payload", 16)]),
+        ]);
+        assert_eq!(s.bytes, 24);
+        assert_eq!(s.find("payload").unwrap().bytes, 16);
+        assert_eq!(s.clone().inline().bytes, 16);
+        assert_eq!(s.bits_per_symbol(12), 16.0);
+        assert_eq!(format!("{}", s).lines().count(), 4);
+    }
+
+    #[test]
+    fn structures() {
+        let bv = BitVec::new(1000);
+        assert_eq!(bv.space().find("payload").unwrap().bytes, 32 * 4);
+        assert_eq!(bv.space_bytes(), bv.size_of());
+
+        let iv = IntVec::new(5, 100);
+        assert_eq!(iv.space().find("payload").unwrap().bytes, 16 * 4);
+        assert_eq!(iv.space_bytes(), iv.size_of());
+
+        let rs = RankSupport::new(BitVec::new(1000));
+        let space = rs.space();
+        assert!(space.find("rank directory").unwrap().bytes > 0);
+        assert_eq!(space.bytes, rs.size_of());
+        assert_eq!(rs.overhead(), space.bits() - 32 * 4 * 8);
+
+        let wt = WT::new("abracadabra");
+        let space = wt.space();
+        assert_eq!(space.find("levels").unwrap().children.len(), 3);
+        assert!(space.find("alphabet table").is_some());
+        assert_eq!(space.bytes, wt.size_of());
+
+        // Single symbol: no levels
+        let wt = WT::new("aaaa");
+        assert_eq!(wt.space().find("levels").unwrap().bytes, 0);
+
+        let bf = BloomFilter::new(3, 640);
+        assert_eq!(bf.space().find("payload").unwrap().bytes, 80);
+        let bbf = BlockedBloomFilter::new(3, 10, 64);
+        assert_eq!(bbf.space().find("payload").unwrap().bytes, 640);
+    }
+}
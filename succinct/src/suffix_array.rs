@@ -0,0 +1,203 @@
+use super::bv::IntVec;
+use super::math::clog;
+
+// Suffix array construction by induced sorting (SA-IS).
+// Nong, Zhang and Chan. "Two Efficient Algorithms for Linear Time Suffix
+// Array Construction"
+
+const EMPTY: usize = usize::MAX;
+
+pub fn sais(text: &[u8]) -> IntVec {
+    // Suffix array of a byte string, bit-packed with width clog(n)
+    let s: Vec<usize> = text.iter().map(|&c| c as usize).collect();
+    pack(&sais_shifted(s, 256))
+}
+
+pub fn sais_int(text: &[u32], sigma: usize) -> IntVec {
+    // Suffix array of a string over the integer alphabet [0, sigma)
+    let s: Vec<usize> = text.iter()
+                            .map(|&c| { assert!((c as usize) < sigma); c as usize })
+                            .collect();
+    pack(&sais_shifted(s, sigma))
+}
+
+fn sais_shifted(mut s: Vec<usize>, sigma: usize) -> Vec<usize> {
+    // Shift the alphabet up by one, append a unique smallest sentinel and
+    // drop it from the result again
+    for c in s.iter_mut() {
+        *c += 1;
+    }
+    s.push(0);
+    let sa = sa_is(&s, sigma + 1);
+    sa[1..].to_vec()
+}
+
+fn pack(sa: &[usize]) -> IntVec {
+    let w = if sa.len() > 1 { clog(sa.len()) } else { 1 };
+    let mut iv = IntVec::new(w, sa.len());
+    for (i, &p) in sa.iter().enumerate() {
+        iv.set_int(i, p as u32);
+    }
+    iv
+}
+
+fn sa_is(s: &[usize], sigma: usize) -> Vec<usize> {
+    // s must end with a unique, smallest symbol 0
+    let n = s.len();
+    if n == 1 { return vec![0] }
+
+    // S/L classification: stype[i] is true iff suffix i is S-type
+    let mut stype = vec![false; n];
+    stype[n - 1] = true;
+    for i in (0..n - 1).rev() {
+        stype[i] = s[i] < s[i + 1] || (s[i] == s[i + 1] && stype[i + 1]);
+    }
+
+    let mut bkt = vec![0_usize; sigma];
+    for &c in s.iter() {
+        bkt[c] += 1;
+    }
+
+    // 1) Place LMS suffixes at the ends of their buckets and induce
+    let mut sa = vec![EMPTY; n];
+    let mut tails = bucket_tails(&bkt);
+    for i in 1..n {
+        if is_lms(&stype, i) {
+            tails[s[i]] -= 1;
+            sa[tails[s[i]]] = i;
+        }
+    }
+    induce(s, &stype, &bkt, &mut sa);
+
+    // 2) Name the (now sorted) LMS substrings
+    let sorted_lms: Vec<usize> = sa.iter().cloned().filter(|&p| is_lms(&stype, p)).collect();
+    let mut names = vec![EMPTY; n];
+    let mut name = 0;
+    for (k, &p) in sorted_lms.iter().enumerate() {
+        if k > 0 && !lms_eq(s, &stype, sorted_lms[k - 1], p) {
+            name += 1;
+        }
+        names[p] = name;
+    }
+
+    // 3) Sort LMS suffixes, recursing on the reduced string if names collide
+    let lms: Vec<usize> = (1..n).filter(|&i| is_lms(&stype, i)).collect();
+    let reduced: Vec<usize> = lms.iter().map(|&p| names[p]).collect();
+    let n_names = name + 1;
+    let reduced_sa = if n_names == reduced.len() {
+        let mut rsa = vec![0; reduced.len()];
+        for (i, &c) in reduced.iter().enumerate() {
+            rsa[c] = i;
+        }
+        rsa
+    } else {
+        sa_is(&reduced, n_names)
+    };
+
+    // 4) Induce the final order from the sorted LMS suffixes
+    for p in sa.iter_mut() {
+        *p = EMPTY;
+    }
+    let mut tails = bucket_tails(&bkt);
+    for &r in reduced_sa.iter().rev() {
+        let p = lms[r];
+        tails[s[p]] -= 1;
+        sa[tails[s[p]]] = p;
+    }
+    induce(s, &stype, &bkt, &mut sa);
+    sa
+}
+
+fn is_lms(stype: &[bool], i: usize) -> bool {
+    i > 0 && i != EMPTY && stype[i] && !stype[i - 1]
+}
+
+fn lms_eq(s: &[usize], stype: &[bool], a: usize, b: usize) -> bool {
+    // Compare the LMS substrings starting at a and b
+    let n = s.len();
+    if a == n - 1 || b == n - 1 { return a == b }
+    let mut k = 0;
+    loop {
+        if s[a + k] != s[b + k] || stype[a + k] != stype[b + k] { return false }
+        if k > 0 {
+            let a_end = is_lms(stype, a + k);
+            let b_end = is_lms(stype, b + k);
+            if a_end || b_end { return a_end && b_end }
+        }
+        k += 1;
+    }
+}
+
+fn bucket_heads(bkt: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    bkt.iter().map(|&c| { let h = sum; sum += c; h }).collect()
+}
+
+fn bucket_tails(bkt: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    bkt.iter().map(|&c| { sum += c; sum }).collect()
+}
+
+fn induce(s: &[usize], stype: &[bool], bkt: &[usize], sa: &mut [usize]) {
+    // Induce L-type suffixes left to right, then S-type right to left
+    let n = s.len();
+    let mut heads = bucket_heads(bkt);
+    for i in 0..n {
+        let j = sa[i];
+        if j != EMPTY && j > 0 && !stype[j - 1] {
+            let c = s[j - 1];
+            sa[heads[c]] = j - 1;
+            heads[c] += 1;
+        }
+    }
+    let mut tails = bucket_tails(bkt);
+    for i in (0..n).rev() {
+        let j = sa[i];
+        if j != EMPTY && j > 0 && stype[j - 1] {
+            let c = s[j - 1];
+            tails[c] -= 1;
+            sa[tails[c]] = j - 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod sa_tests {
+    use crate::suffix_array::*;
+    use rand::Rng;
+
+    fn naive<T: Ord>(t: &[T]) -> Vec<u32> {
+        let mut sa: Vec<usize> = (0..t.len()).collect();
+        sa.sort_by(|&a, &b| t[a..].cmp(&t[b..]));
+        sa.iter().map(|&p| p as u32).collect()
+    }
+
+    #[test]
+    fn bytes() {
+        for s in &["abracadabra", "mississippi", "yabadabadoo", "aaaaaaa", "a", ""] {
+            let sa = sais(s.as_bytes());
+            assert_eq!(sa.to_vec(), naive(s.as_bytes()));
+        }
+        let sa = sais(b"banana");
+        assert_eq!(sa.to_vec(), vec![5, 3, 1, 0, 4, 2]);
+        assert_eq!(sa.w_size(), clog(6));
+    }
+
+    #[test]
+    fn ints() {
+        let t = vec![2, 0, 1, 0, 1, 0, 2, 2, 1];
+        assert_eq!(sais_int(&t, 3).to_vec(), naive(&t));
+    }
+
+    #[test]
+    fn fuzz() {
+        let mut rng = rand::thread_rng();
+        for sigma in &[2_u32, 4, 16, 256] {
+            for _ in 0..20 {
+                let n = rng.gen_range(0, 500);
+                let t: Vec<u32> = (0..n).map(|_| rng.gen_range(0, sigma)).collect();
+                assert_eq!(sais_int(&t, *sigma as usize).to_vec(), naive(&t));
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use super::bv::{BitVec, IntVec};
+use super::rank_select::RankSupport;
+use super::louds::Louds;
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trie {
+    // Static LOUDS trie over byte strings. Nodes are in BFS order; node v
+    // is reached by an edge labelled labels[v]. Key ids are the ranks of
+    // the terminal nodes in BFS order.
+    louds: Louds,
+    labels: IntVec,
+    terminal: RankSupport,
+    n_keys: usize,
+}
+
+pub struct PrefixIter<'a> {
+    trie: &'a Trie,
+    stack: Vec<(usize, usize)>, // (node, key length at node)
+    key: Vec<u8>,
+    plen: usize,
+}
+
+impl Trie {
+    pub fn new<S: AsRef<str>>(keys: &[S]) -> Self {
+        // Build from a sorted list of keys. Duplicates are ignored.
+        let keys: Vec<&[u8]> = keys.iter().map(|k| k.as_ref().as_bytes()).collect();
+        assert!(keys.windows(2).all(|w| w[0] <= w[1]), "keys must be sorted");
+
+        // Each node covers a range of keys sharing a prefix of length depth
+        let mut children: Vec<Vec<usize>> = vec![];
+        let mut labels = vec![0_u8];
+        let mut terminal = vec![];
+        let mut queue = VecDeque::new();
+        queue.push_back((0, keys.len(), 0));
+        while let Some((lo, hi, depth)) = queue.pop_front() {
+            let mut lo = lo;
+            let is_key = lo < hi && keys[lo].len() == depth;
+            terminal.push(is_key);
+            while lo < hi && keys[lo].len() == depth {
+                lo += 1;
+            }
+
+            let mut kids = vec![];
+            while lo < hi {
+                let c = keys[lo][depth];
+                let mut end = lo;
+                while end < hi && keys[end][depth] == c {
+                    end += 1;
+                }
+                kids.push(labels.len());
+                labels.push(c);
+                queue.push_back((lo, end, depth + 1));
+                lo = end;
+            }
+            children.push(kids);
+        }
+
+        let n = labels.len();
+        let mut term_bv = BitVec::new(n);
+        for (v, &t) in terminal.iter().enumerate() {
+            term_bv.set(v, t);
+        }
+        let n_keys = terminal.iter().filter(|&&t| t).count();
+        let labels = labels.iter().map(|&c| c as u32).collect();
+
+        Self {
+            louds: Louds::from_children(&children, 0),
+            labels: IntVec::from_vec(&labels, 8),
+            terminal: RankSupport::new(term_bv),
+            n_keys,
+        }
+    }
+
+    fn label(&self, v: usize) -> u8 {
+        self.labels.get_int(v) as u8
+    }
+
+    fn is_terminal(&self, v: usize) -> bool {
+        self.terminal.get(v)
+    }
+
+    fn find_child(&self, v: usize, c: u8) -> Option<usize> {
+        // Children are sorted by label, so binary search them
+        let mut range = self.louds.children(v);
+        while range.start < range.end {
+            let mid = range.start + (range.end - range.start) / 2;
+            let l = self.label(mid);
+            if l == c {
+                return Some(mid);
+            } else if l < c {
+                range.start = mid + 1;
+            } else {
+                range.end = mid;
+            }
+        }
+        None
+    }
+
+    fn find(&self, s: &[u8]) -> Option<usize> {
+        // Node reached by spelling s from the root
+        let mut v = self.louds.root();
+        for &c in s.iter() {
+            v = self.find_child(v, c)?;
+        }
+        Some(v)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.id_of(key).is_some()
+    }
+
+    pub fn id_of(&self, key: &str) -> Option<usize> {
+        let v = self.find(key.as_bytes())?;
+        if self.is_terminal(v) {
+            Some(self.terminal.rank1(v) - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn key_of(&self, id: usize) -> Option<String> {
+        if id >= self.n_keys { return None }
+        let mut v = self.terminal.select1(id + 1).unwrap();
+        let mut key = vec![];
+        while let Some(p) = self.louds.parent(v) {
+            key.push(self.label(v));
+            v = p;
+        }
+        key.reverse();
+        Some(String::from_utf8(key).unwrap())
+    }
+
+    pub fn prefix_iter(&self, prefix: &str) -> PrefixIter<'_> {
+        // Keys starting with prefix, in lexicographic order
+        let stack = match self.find(prefix.as_bytes()) {
+            Some(v) => vec![(v, prefix.len())],
+            None => vec![],
+        };
+        PrefixIter {
+            trie: self,
+            stack,
+            key: prefix.as_bytes().to_vec(),
+            plen: prefix.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        // Number of keys
+        self.n_keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_keys == 0
+    }
+
+    pub fn n_nodes(&self) -> usize {
+        self.louds.n_nodes()
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        let mut size = std::mem::size_of::<Self>();
+        size += self.louds.size_of();
+        size += self.labels.size_of();
+        size += self.terminal.size_of();
+        size
+    }
+}
+
+impl Iterator for PrefixIter<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while let Some((v, len)) = self.stack.pop() {
+            if len > self.plen {
+                self.key.truncate(len - 1);
+                self.key.push(self.trie.label(v));
+            }
+            for c in self.trie.louds.children(v).rev() {
+                self.stack.push((c, len + 1));
+            }
+            if self.trie.is_terminal(v) {
+                return Some(String::from_utf8(self.key.clone()).unwrap());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod trie_tests {
+    use crate::trie::*;
+
+    fn keys() -> Vec<&'static str> {
+        vec!["", "a", "ab", "abc", "abd", "b", "ba", "bcd", "tomorrow", "tomorrows"]
+    }
+
+    #[test]
+    fn contains() {
+        let t = Trie::new(&keys());
+        assert_eq!(t.len(), 10);
+        for k in keys() {
+            assert!(t.contains(k));
+        }
+        for k in &["abcd", "bc", "c", "tom", "tomorrowz"] {
+            assert!(!t.contains(k));
+        }
+    }
+
+    #[test]
+    fn ids() {
+        let t = Trie::new(&keys());
+        let mut seen = vec![false; t.len()];
+        for k in keys() {
+            let id = t.id_of(k).unwrap();
+            assert!(!seen[id]);
+            seen[id] = true;
+            assert_eq!(t.key_of(id).unwrap(), k);
+        }
+        assert_eq!(t.id_of("abcd"), None);
+        assert_eq!(t.key_of(t.len()), None);
+    }
+
+    #[test]
+    fn prefix() {
+        let t = Trie::new(&keys());
+        let ks: Vec<String> = t.prefix_iter("ab").collect();
+        assert_eq!(ks, vec!["ab", "abc", "abd"]);
+        let ks: Vec<String> = t.prefix_iter("").collect();
+        assert_eq!(ks, keys());
+        let ks: Vec<String> = t.prefix_iter("tomo").collect();
+        assert_eq!(ks, vec!["tomorrow", "tomorrows"]);
+        assert_eq!(t.prefix_iter("x").count(), 0);
+        assert_eq!(t.prefix_iter("bc").count(), 1);
+    }
+
+    #[test]
+    fn degenerate() {
+        let t = Trie::new(&["x", "x", "y"]);
+        assert_eq!(t.len(), 2);
+        let empty: Vec<&str> = vec![];
+        let t = Trie::new(&empty);
+        assert!(t.is_empty());
+        assert!(!t.contains(""));
+        assert_eq!(t.prefix_iter("").count(), 0);
+    }
+}
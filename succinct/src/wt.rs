@@ -1,6 +1,10 @@
 use super::bv::{IntVec, BitVec};
 use super::rank_select::RankSupport;
-use super::math::{clog};
+use super::math::{cdiv, clog, exp2};
+use std::cmp::{max, min};
+use std::thread;
+use std::io::{self, Read};
+use super::space::{SpaceBreakdown, SpaceUsage};
 use serde::{Serialize, Deserialize};
 // use std::str;
 
@@ -47,10 +51,11 @@ impl<'a> WTBuilder<'a> {
         let bv = vec![BitVec::new(n); l];
         
         // sigma log(n) bits for HIST...
-        let hist = IntVec::new(clog(n), 2_usize.pow(l as u32) as usize); //oversize if log is not round
+        let w = max(clog(n), 1); // still need 1 bit when n == 1
+        let hist = IntVec::new(w, 2_usize.pow(l as u32) as usize); //oversize if log is not round
 
         // sigma log(n) bits for starting positions of blocks
-        let spos = IntVec::new(clog(n), 2_usize.pow(l as u32) as usize); //oversize if log is not round
+        let spos = IntVec::new(w, 2_usize.pow(l as u32) as usize); //oversize if log is not round
         Self {
             s: s,
             n: n,
@@ -123,6 +128,80 @@ impl<'a> WTBuilder<'a> {
         self
     }
 
+    pub fn build_parallel(&mut self, n_threads: usize) -> &Self {
+        // Domain decomposition variant (ddWT) of the same paper. Each thread
+        // builds the levels for its chunk of the text, keeping a histogram of
+        // the prefixes per level. Level li is then merged by copying, for each
+        // prefix in order, the matching segment of every chunk in text order,
+        // which gives the same bits as the sequential build.
+        assert!(n_threads > 0);
+        if self.n_chars == 1 || self.n == 0 { return self }
+
+        let char_table = &self.char_table;
+        let l = self.l;
+        let chunk = cdiv(self.n, n_threads);
+        let parts: Vec<(Vec<BitVec>, Vec<Vec<usize>>)> = thread::scope(|scope| {
+            let handles: Vec<_> = self.s.as_bytes()
+                .chunks(chunk)
+                .map(|part| scope.spawn(move || Self::build_chunk(char_table, l, part)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let n = self.n;
+        let parts = &parts;
+        self.bv = thread::scope(|scope| {
+            let handles: Vec<_> = (0..l)
+                .map(|li| scope.spawn(move || Self::merge_level(parts, li, n)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        self
+    }
+
+    fn build_chunk(char_table: &CharTable, l: usize, part: &[u8]) -> (Vec<BitVec>, Vec<Vec<usize>>) {
+        // Levels and per level prefix histograms for one chunk of the text
+        let mut bv = Vec::with_capacity(l);
+        let mut hist = Vec::with_capacity(l);
+        for li in 0..l {
+            let prefix = |c: char| if li == 0 { 0 } else { char_table.get_prefix(li, c) };
+            let mut h = vec![0; exp2(li)];
+            for &c in part.iter() {
+                h[prefix(c as char)] += 1;
+            }
+
+            let mut spos = vec![0; exp2(li)];
+            for p in 1..spos.len() {
+                spos[p] = spos[p - 1] + h[p - 1];
+            }
+            let mut level = BitVec::new(part.len());
+            for &c in part.iter() {
+                let p = prefix(c as char);
+                level.set(spos[p], char_table.get_bit(li, c as char));
+                spos[p] += 1;
+            }
+            bv.push(level);
+            hist.push(h);
+        }
+        (bv, hist)
+    }
+
+    fn merge_level(parts: &[(Vec<BitVec>, Vec<Vec<usize>>)], li: usize, n: usize) -> BitVec {
+        let mut level = BitVec::new(n);
+        let mut starts = vec![0; parts.len()]; // next unread bit of each chunk
+        let mut pos = 0;
+        for p in 0..exp2(li) {
+            for (t, (bv, hist)) in parts.iter().enumerate() {
+                let len = hist[li][p];
+                copy_bits(&bv[li], starts[t], &mut level, pos, len);
+                starts[t] += len;
+                pos += len;
+            }
+        }
+        assert_eq!(pos, n);
+        level
+    }
+
     pub fn finish(&self) -> WT {    
         // Complete the construction. Create rank supported bit vectors.
         let mut bv = vec![];
@@ -144,12 +223,138 @@ impl<'a> WTBuilder<'a> {
     }
 }
 
+pub struct WTStreamBuilder<F> {
+    // Builds a WT from a byte stream without holding the text in memory.
+    // `open` must return a fresh reader over the same bytes each time it is
+    // called: the first pass collects the alphabet and histogram, the
+    // second writes every level at once from per level start positions.
+    open: F,
+    char_table: CharTable,
+    n: usize,
+    hist: Vec<usize>, // occurrences of each encoded char
+    bv: Vec<BitVec>,
+}
+
+impl<F, R> WTStreamBuilder<F>
+where
+    F: Fn() -> io::Result<R>,
+    R: Read,
+{
+    pub fn new(open: F) -> io::Result<Self> {
+        let mut counts = [0_usize; 128];
+        let mut n = 0;
+        for_each_chunk(open()?, |chunk| {
+            for &c in chunk.iter() {
+                if !c.is_ascii() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "input is not ascii"));
+                }
+                counts[c as usize] += 1;
+            }
+            n += chunk.len();
+            Ok(())
+        })?;
+        if n == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "input is empty"));
+        }
+
+        let alphabet: String = (0..128_u8).filter(|&c| counts[c as usize] > 0).map(|c| c as char).collect();
+        let char_table = CharTable::new(&alphabet);
+        let hist = alphabet.chars().map(|c| counts[c as usize]).collect();
+        Ok(Self {
+            open,
+            bv: vec![BitVec::new(n); char_table.width()],
+            char_table,
+            n,
+            hist,
+        })
+    }
+
+    pub fn build(&mut self) -> io::Result<()> {
+        let width = self.char_table.width();
+
+        // spos[li][p]: next free position for prefix p at level li
+        let mut spos: Vec<Vec<usize>> = Vec::with_capacity(width);
+        for li in 0..width {
+            let mut level = vec![0; exp2(li) + 1];
+            for (c_i, &h) in self.hist.iter().enumerate() {
+                level[(c_i >> (width - li)) + 1] += h;
+            }
+            for p in 1..level.len() {
+                level[p] += level[p - 1];
+            }
+            spos.push(level);
+        }
+
+        let char_table = &self.char_table;
+        let bv = &mut self.bv;
+        let n = self.n;
+        let mut read = 0;
+        for_each_chunk((self.open)()?, |chunk| {
+            read += chunk.len();
+            if read > n {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "input changed between passes"));
+            }
+            for &c in chunk.iter() {
+                let c_i = char_table.i(c as char);
+                for li in 0..width {
+                    let p = c_i >> (width - li);
+                    bv[li].set(spos[li][p], (c_i >> (width - 1 - li)) & 1 == 1);
+                    spos[li][p] += 1;
+                }
+            }
+            Ok(())
+        })?;
+        if read != n {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "input changed between passes"));
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> WT {
+        // Levels move into the WT, so peak memory stays at one copy
+        WT {
+            n: self.n,
+            bv: self.bv.into_iter().map(RankSupport::new).collect(),
+            char_table: self.char_table,
+        }
+    }
+}
+
+fn for_each_chunk<R: Read, G: FnMut(&[u8]) -> io::Result<()>>(mut reader: R, mut f: G) -> io::Result<()> {
+    // Feed the reader to f in fixed size chunks
+    let mut buf = vec![0_u8; 1 << 16];
+    loop {
+        let k = match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(k) => k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        f(&buf[..k])?;
+    }
+}
+
 impl WT {
     pub fn new(s: &str)  -> Self {
         assert!(s.is_ascii());
         WTBuilder::new(s).build().finish()
     }
 
+    pub fn from_reader<F, R>(open: F) -> io::Result<Self>
+    where
+        F: Fn() -> io::Result<R>,
+        R: Read,
+    {
+        let mut sb = WTStreamBuilder::new(open)?;
+        sb.build()?;
+        Ok(sb.finish())
+    }
+
+    pub fn new_parallel(s: &str, n_threads: usize) -> Self {
+        assert!(s.is_ascii());
+        WTBuilder::new(s).build_parallel(n_threads).finish()
+    }
+
     pub fn access(&self, i: usize) -> char {
         assert!(i < self.n);
         if self.bv.len() == 0 { return self.char_table.get_char(0)}
@@ -254,12 +459,139 @@ impl WT {
         Some(curr_index)
     } 
 
+    pub fn contains_symbol(&self, c: char) -> bool {
+        self.char_table.in_charset(c)
+    }
+
+    pub fn alphabet(&self) -> Vec<char> {
+        // Distinct chars of the text in ascending order
+        (0..self.n_chars()).map(|c_i| self.char_table.get_char(c_i)).collect()
+    }
+
+    pub fn count(&self, c: char) -> usize {
+        // Occurrences of c in the text: the size of its leaf
+        if !self.contains_symbol(c) { return 0 }
+        let (_, l, r) = self.path(c);
+        r - l
+    }
+
+    pub fn histogram(&self) -> Vec<(char, usize)> {
+        // Occurrences of every char of the alphabet, in ascending order
+        self.rank_all(self.n - 1)
+    }
+
+    fn path(&self, c: char) -> (Vec<(usize, bool)>, usize, usize) {
+        // Node offset and branch bit of c at every level, and c's leaf [l, r)
+        let mut l = 0;
+        let mut r = self.n;
+        let mut path = Vec::with_capacity(self.bv.len());
+        for (i, bv) in self.bv.iter().enumerate() {
+            let curr_bit = self.char_table.get_bit(i, c);
+            path.push((l, curr_bit));
+            if curr_bit {
+                l += bv.rel_rank(false, l, r - l - 1);
+            } else {
+                r -= bv.rel_rank(true, l, r - l - 1);
+            }
+        }
+        (path, l, r)
+    }
+
+    pub fn rank_many(&self, c: char, positions: &[usize]) -> Vec<usize> {
+        // rank(c, i) for every i. The node boundaries on c's path are
+        // computed once and shared by all queries.
+        let (path, _, _) = self.path(c);
+        positions.iter().map(|&i| {
+            assert!(i < self.n);
+            let mut curr_rank = i + 1;
+            for (k, &(l, curr_bit)) in path.iter().enumerate() {
+                curr_rank = self.bv[k].rel_rank(curr_bit, l, curr_rank - 1);
+                if curr_rank == 0 { return 0 }
+            }
+            curr_rank
+        }).collect()
+    }
+
+    pub fn select_many(&self, c: char, ranks: &[usize]) -> Vec<Option<usize>> {
+        // select(c, r) for every r, sharing c's path like rank_many
+        let (path, l, r) = self.path(c);
+        ranks.iter().map(|&rank| {
+            if rank == 0 || rank > r - l { return None }
+            let mut curr_index = rank - 1;
+            for (k, &(l, curr_bit)) in path.iter().enumerate().rev() {
+                curr_index = self.bv[k].rel_select(curr_bit, l, curr_index + 1)?;
+            }
+            Some(curr_index)
+        }).collect()
+    }
+
+    pub fn access_many(&self, positions: &[usize]) -> Vec<char> {
+        // access(i) for every i. Queries are routed down the tree together,
+        // so each node's boundaries are computed once.
+        let queries: Vec<(usize, usize)> = positions.iter()
+            .map(|&i| { assert!(i < self.n); i })
+            .enumerate()
+            .collect();
+        let mut out = vec!['\0'; positions.len()];
+        self.access_node(0, 0, self.n, 0, &queries, &mut out);
+        out
+    }
+
+    fn access_node(&self, level: usize, l: usize, r: usize, char_i: usize,
+                   queries: &[(usize, usize)], out: &mut [char]) {
+        // queries are (output slot, offset within the node [l, r))
+        if level == self.bv.len() {
+            let c = self.char_table.get_char(char_i);
+            for &(j, _) in queries.iter() {
+                out[j] = c;
+            }
+            return
+        }
+        let bv = &self.bv[level];
+        let zeros = bv.rel_rank(false, l, r - l - 1);
+        let mut left = vec![];
+        let mut right = vec![];
+        for &(j, p) in queries.iter() {
+            let curr_bit = bv.get(l + p);
+            let child = bv.rel_rank(curr_bit, l, p) - 1;
+            if curr_bit { right.push((j, child)) } else { left.push((j, child)) }
+        }
+        if !left.is_empty() {
+            self.access_node(level + 1, l, l + zeros, char_i << 1, &left, out);
+        }
+        if !right.is_empty() {
+            self.access_node(level + 1, l + zeros, r, (char_i << 1) + 1, &right, out);
+        }
+    }
+
+    pub fn rank_all(&self, i: usize) -> Vec<(char, usize)> {
+        // rank(c, i) for every char c of the alphabet in one traversal,
+        // visiting only nodes with a non-zero count
+        assert!(i < self.n);
+        let mut out: Vec<(char, usize)> = (0..self.n_chars())
+            .map(|c_i| (self.char_table.get_char(c_i), 0))
+            .collect();
+        self.rank_all_node(0, 0, self.n, 0, i + 1, &mut out);
+        out
+    }
+
+    fn rank_all_node(&self, level: usize, l: usize, r: usize, char_i: usize,
+                     curr_rank: usize, out: &mut [(char, usize)]) {
+        if curr_rank == 0 { return }
+        if level == self.bv.len() {
+            out[char_i].1 = curr_rank;
+            return
+        }
+        let bv = &self.bv[level];
+        let zeros = bv.rel_rank(false, l, r - l - 1);
+        let ones = bv.rel_rank(true, l, curr_rank - 1);
+        self.rank_all_node(level + 1, l, l + zeros, char_i << 1, curr_rank - ones, out);
+        self.rank_all_node(level + 1, l + zeros, r, (char_i << 1) + 1, ones, out);
+    }
+
     pub fn size_of(&self) -> usize {
         // Size of struct in bytes
-        let mut size = std::mem::size_of::<Self>();
-        size += self.bv.len() * self.bv[0].size_of();
-        size += self.char_table.size_of();
-        size
+        self.space().bytes
     }
 
     pub fn n_chars(&self) -> usize {
@@ -321,15 +653,51 @@ impl CharTable {
     }
 
     pub fn size_of(&self) -> usize {
-        let mut size = std::mem::size_of::<Self>();
-        size += self.rs.size_of();
-        size
+        self.space().bytes
     }
 
     pub fn n_chars(&self) -> usize {
         // Size of encoded alphabet
         self.rs.rank1(127)
     }
+
+    pub fn width(&self) -> usize {
+        // Bits per encoded char, i.e. number of wavelet tree levels
+        self.width
+    }
+}
+
+impl SpaceUsage for WT {
+    fn space(&self) -> SpaceBreakdown {
+        let levels = self.bv.iter()
+            .enumerate()
+            .map(|(i, bv)| bv.space().named(&format!("level {}", i)))
+            .collect();
+        SpaceBreakdown::node("WT", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::node("levels", levels),
+            self.char_table.space().inline(),
+        ])
+    }
+}
+
+impl SpaceUsage for CharTable {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("alphabet table", vec![
+            SpaceBreakdown::header::<Self>(),
+            self.rs.space().inline(),
+        ])
+    }
+}
+
+fn copy_bits(src: &BitVec, from: usize, dst: &mut BitVec, to: usize, len: usize) {
+    // Copy src[from..from + len) to dst[to..to + len), 32 bits at a time
+    let mut k = 0;
+    while k < len {
+        let w = min(32, len - k);
+        dst.set_int(to + k, src.get_int(from + k, w), w);
+        k += w;
+    }
 }
 
 pub fn count_chars(s: &str) -> usize {
@@ -367,6 +735,58 @@ mod wt_tests {
 
     }
 
+    #[test]
+    fn introspection() {
+        let wt = WT::new("tomorrow and tomorrow");
+        assert_eq!(wt.alphabet(), vec![' ', 'a', 'd', 'm', 'n', 'o', 'r', 't', 'w']);
+        assert_eq!(wt.count('o'), 6);
+        assert_eq!(wt.count('r'), 4);
+        assert_eq!(wt.count('z'), 0);
+        assert!(wt.contains_symbol('w'));
+        assert!(!wt.contains_symbol('z'));
+        let hist = wt.histogram();
+        assert_eq!(hist.len(), wt.n_chars());
+        assert_eq!(hist.iter().map(|&(_, k)| k).sum::<usize>(), wt.len());
+        for (c, k) in hist {
+            assert_eq!(wt.count(c), k);
+        }
+
+        let wt = WT::new("zzz");
+        assert_eq!(wt.histogram(), vec![('z', 3)]);
+        assert_eq!(wt.count('z'), 3);
+    }
+
+    #[test]
+    fn batch() {
+        for s in &["abracadabra", "tomorrow and tomorrow and tomorrow", "aaaa"] {
+            let wt = WT::new(s);
+            let n = s.len();
+            let positions: Vec<usize> = (0..n).rev().chain(0..n).collect();
+            let chars: Vec<char> = positions.iter().map(|&i| wt.access(i)).collect();
+            assert_eq!(wt.access_many(&positions), chars);
+
+            for c in s.chars() {
+                let ranks: Vec<usize> = positions.iter().map(|&i| wt.rank(c, i)).collect();
+                assert_eq!(wt.rank_many(c, &positions), ranks);
+                let total = wt.rank(c, n - 1);
+                let sel: Vec<Option<usize>> = (0..total + 2)
+                    .map(|r| if r == 0 || r > total { None } else { wt.select(c, r) })
+                    .collect();
+                let rs: Vec<usize> = (0..total + 2).collect();
+                assert_eq!(wt.select_many(c, &rs), sel);
+            }
+
+            for i in 0..n {
+                for (c, r) in wt.rank_all(i) {
+                    assert_eq!(r, wt.rank(c, i));
+                }
+            }
+        }
+        let wt = WT::new("abracadabra");
+        assert_eq!(wt.rank_all(3), vec![('a', 2), ('b', 1), ('c', 0), ('d', 0), ('r', 1)]);
+        assert!(wt.access_many(&[]).is_empty());
+    }
+
     #[test]
     fn select() {
         let s = "abracadabra";
@@ -497,6 +917,52 @@ mod wtbuilder_tests {
         wtb.build();
     }
 
+    #[test]
+    fn build_parallel() {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let mut texts = vec!["0167154263".to_string(), "ab".to_string(), "aaaa".to_string()];
+        texts.push((0..5000).map(|_| rng.gen_range(32, 127) as u8 as char).collect());
+        texts.push((0..777).map(|_| rng.gen_range(65, 70) as u8 as char).collect());
+        for s in texts.iter() {
+            let mut seq = WTBuilder::new(s);
+            seq.build();
+            for &t in &[1, 2, 3, 8, 64] {
+                let mut par = WTBuilder::new(s);
+                par.build_parallel(t);
+                assert_eq!(seq.bv.len(), par.bv.len());
+                for (a, b) in seq.bv.iter().zip(par.bv.iter()) {
+                    assert_eq!(a.to_vec(), b.to_vec());
+                }
+            }
+        }
+
+        let wt = WT::new_parallel("mississippi", 4);
+        assert_eq!(wt.rank('s', 6), 4);
+        assert_eq!(wt.access(10), 'i');
+    }
+
+    #[test]
+    fn stream() {
+        use std::io::Cursor;
+        for s in &["0167154263", "dccbbbaaaa", "x", "the quick brown fox jumps over the lazy dog\n"] {
+            let mut seq = WTBuilder::new(s);
+            seq.build();
+            let mut sb = WTStreamBuilder::new(|| Ok(Cursor::new(s.as_bytes()))).unwrap();
+            sb.build().unwrap();
+            assert_eq!(seq.bv.len(), sb.bv.len());
+            for (a, b) in seq.bv.iter().zip(sb.bv.iter()) {
+                assert_eq!(a.to_vec(), b.to_vec());
+            }
+        }
+
+        let wt = WT::from_reader(|| Ok(Cursor::new("mississippi"))).unwrap();
+        assert_eq!(wt.len(), 11);
+        assert_eq!(wt.select('p', 2), Some(9));
+        assert!(WT::from_reader(|| Ok(Cursor::new(""))).is_err());
+        assert!(WT::from_reader(|| Ok(Cursor::new("caf\u{e9}"))).is_err());
+    }
+
     #[test]
     fn init_hist() {
         let s = "dccbbbaaaa";
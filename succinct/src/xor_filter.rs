@@ -0,0 +1,280 @@
+use super::bv::IntVec;
+use super::bloom_filter::{FilterError, Query};
+use super::hashing::{seeded_hash, seeds_from, FilterHasher, SeaHash};
+use super::math::cdiv;
+use super::space::{SpaceBreakdown, SpaceUsage};
+
+use rand::Rng;
+use std::cmp::max;
+use std::hash::Hash;
+use serde::{Serialize, Deserialize};
+
+// Table size is 1.23 n + EXTRA slots, split into three segments
+const EXTRA: usize = 32;
+// Seeds tried before construction gives up
+const MAX_ATTEMPTS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct XorFilter<F = SeaHash> {
+    // Static xor filter (Graf & Lemire). Every key hashes to one slot in each
+    // of three segments of `fps`; construction assigns the slots so that the
+    // xor of a key's three slots equals its w bit fingerprint. A query is
+    // three lookups, and the false positive rate is 2^-w.
+    fps: IntVec,
+    seg: usize,
+    seeds: Vec<u64>,
+    seed: u64,
+    n: usize,
+    hasher: F,
+}
+
+impl<F: FilterHasher> Query for XorFilter<F> {
+    fn query<H: Hash>(&self, item: &H) -> bool {
+        let h = self.hash(item);
+        let [h0, h1, h2] = self.slots(h);
+        let x = self.fps.get_int(h0) ^ self.fps.get_int(h1) ^ self.fps.get_int(h2);
+        x == self.fingerprint(h)
+    }
+}
+
+impl XorFilter {
+    pub fn new<H: Hash>(keys: &[H]) -> Result<Self, FilterError> {
+        /* Create xor filter with 8 bit fingerprints, fpr ~ 1/256 */
+        Self::with_fp_bits(keys, 8)
+    }
+
+    pub fn with_fpr<H: Hash>(keys: &[H], fpr: f32) -> Result<Self, FilterError> {
+        /* Create xor filter over `keys` with fp rate at most `fpr` */
+        let w = (1.0 / fpr).log2().ceil() as usize;
+        Self::with_fp_bits(keys, w.clamp(1, 32))
+    }
+
+    pub fn with_fp_bits<H: Hash>(keys: &[H], fp_bits: usize) -> Result<Self, FilterError> {
+        Self::with_seed(keys, fp_bits, rand::thread_rng().gen::<u64>())
+    }
+
+    pub fn with_seed<H: Hash>(keys: &[H], fp_bits: usize, seed: u64) -> Result<Self, FilterError> {
+        Self::with_hasher(keys, fp_bits, seed, SeaHash)
+    }
+}
+
+impl<F: FilterHasher> XorFilter<F> {
+    pub fn with_hasher<H: Hash>(keys: &[H], fp_bits: usize, seed: u64, hasher: F) -> Result<Self, FilterError> {
+        // Build over the distinct keys. Peeling fails with small probability
+        // (or always, if two keys share all three slots), in which case the
+        // keys are rehashed with the next seed of seeds_from(seed, ..);
+        // BuildFailed after MAX_ATTEMPTS. The same seed and keys give the
+        // same filter.
+        assert!(fp_bits > 0 && fp_bits <= 32);
+        let capacity = (1.23 * keys.len() as f64) as usize + EXTRA;
+        let seg = cdiv(capacity, 3);
+
+        for attempt_seed in seeds_from(seed, MAX_ATTEMPTS) {
+            let mut xf = Self {
+                fps: IntVec::new(fp_bits, 3 * seg),
+                seg,
+                seeds: seeds_from(attempt_seed, 4),
+                seed: attempt_seed,
+                n: 0,
+                hasher: hasher.clone(),
+            };
+            let mut hashes: Vec<u64> = keys.iter().map(|k| xf.hash(k)).collect();
+            hashes.sort_unstable();
+            hashes.dedup();
+            xf.n = hashes.len();
+            if xf.assign(&hashes) {
+                return Ok(xf)
+            }
+        }
+        Err(FilterError::BuildFailed)
+    }
+
+    fn assign(&mut self, hashes: &[u64]) -> bool {
+        // Peel slots hit by a single remaining key, then assign fingerprints
+        // in reverse peeling order
+        let m = 3 * self.seg;
+        let mut count = vec![0_u32; m];
+        let mut xor = vec![0_u64; m];
+        for &h in hashes.iter() {
+            for s in self.slots(h).iter() {
+                count[*s] += 1;
+                xor[*s] ^= h;
+            }
+        }
+
+        let mut queue: Vec<usize> = (0..m).filter(|&s| count[s] == 1).collect();
+        let mut stack: Vec<(u64, usize)> = Vec::with_capacity(hashes.len());
+        while let Some(s) = queue.pop() {
+            if count[s] != 1 { continue }
+            let h = xor[s];
+            stack.push((h, s));
+            for t in self.slots(h).iter() {
+                count[*t] -= 1;
+                xor[*t] ^= h;
+                if count[*t] == 1 {
+                    queue.push(*t);
+                }
+            }
+        }
+        if stack.len() != hashes.len() {
+            return false
+        }
+
+        for &(h, s) in stack.iter().rev() {
+            let [h0, h1, h2] = self.slots(h);
+            let x = self.fps.get_int(h0) ^ self.fps.get_int(h1) ^ self.fps.get_int(h2);
+            self.fps.set_int(s, x ^ self.fingerprint(h));
+        }
+        true
+    }
+
+    fn hash<H: Hash>(&self, item: &H) -> u64 {
+        seeded_hash(&self.hasher, &self.seeds, 0, item)
+    }
+
+    fn slots(&self, h: u64) -> [usize; 3] {
+        // One slot per segment, from three rotations of the hash mapped to
+        // [0, seg) by multiply-shift
+        let reduce = |x: u64| ((x as u32 as u64 * self.seg as u64) >> 32) as usize;
+        [
+            reduce(h),
+            self.seg + reduce(h.rotate_left(21)),
+            2 * self.seg + reduce(h.rotate_left(42)),
+        ]
+    }
+
+    fn fingerprint(&self, h: u64) -> u32 {
+        let w = self.fp_bits();
+        let x = (h ^ (h >> 32)) as u32;
+        if w == 32 { x } else { x & ((1 << w) - 1) }
+    }
+
+    pub fn len(&self) -> usize {
+        // Number of distinct keys
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        // Number of fingerprint slots
+        self.fps.len()
+    }
+
+    pub fn fp_bits(&self) -> usize {
+        self.fps.w_size()
+    }
+
+    pub fn seed(&self) -> u64 {
+        // Seed of the attempt that succeeded
+        self.seed
+    }
+
+    pub fn bits_per_key(&self) -> f64 {
+        (self.capacity() * self.fp_bits()) as f64 / max(self.n, 1) as f64
+    }
+
+    pub fn size_of(&self) -> usize {
+        // Size of struct in bytes
+        self.space().bytes
+    }
+}
+
+impl<F: FilterHasher> SpaceUsage for XorFilter<F> {
+    fn space(&self) -> SpaceBreakdown {
+        SpaceBreakdown::node("XorFilter", vec![
+            SpaceBreakdown::header::<Self>(),
+            SpaceBreakdown::leaf("payload", self.fps.space().inline().bytes),
+            SpaceBreakdown::leaf("seeds", std::mem::size_of_val::<[u64]>(&self.seeds)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod xor_filter_tests {
+    use crate::xor_filter::*;
+    use std::hash::Hasher;
+    use crate::hashing::MultiplyShift;
+
+    #[derive(Debug, Clone, Default)]
+    struct Identity;
+
+    struct IdentityHasher(u64);
+
+    impl FilterHasher for Identity {
+        // Ignores the seeds: every attempt sees the same hashes
+        type State = IdentityHasher;
+        fn with_seeds(&self, _seeds: &[u64]) -> IdentityHasher {
+            IdentityHasher(0)
+        }
+    }
+
+    impl Hasher for IdentityHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for chunk in bytes.chunks(8) {
+                let mut word = [0_u8; 8];
+                word[..chunk.len()].copy_from_slice(chunk);
+                self.0 = self.0.rotate_left(8) ^ u64::from_le_bytes(word);
+            }
+        }
+
+        fn write_u64(&mut self, x: u64) {
+            self.0 = x;
+        }
+    }
+
+    #[test]
+    fn query() {
+        let keys: Vec<usize> = (0..10000).collect();
+        let xf = XorFilter::new(&keys).unwrap();
+        assert_eq!(xf.len(), 10000);
+        assert_eq!(xf.fp_bits(), 8);
+        assert!(xf.bits_per_key() < 10.0);
+        for k in keys.iter() {
+            assert!(xf.query(k));
+        }
+        let fp = (10000..110000).filter(|i| xf.query(i)).count();
+        assert!(fp < 600, "{} false positives", fp);
+    }
+
+    #[test]
+    fn fpr_and_duplicates() {
+        let keys = vec!["a", "b", "a", "c", "b"];
+        let xf = XorFilter::with_fpr(&keys, 0.001).unwrap();
+        assert_eq!(xf.len(), 3);
+        assert_eq!(xf.fp_bits(), 10);
+        for k in keys.iter() {
+            assert!(xf.query(k));
+        }
+
+        let xf = XorFilter::with_fp_bits(&keys, 32).unwrap();
+        assert!(xf.query(&"c"));
+        assert!(!xf.query(&"d"));
+
+        let a = XorFilter::with_seed(&keys, 8, 11).unwrap();
+        let b = XorFilter::with_seed(&keys, 8, 11).unwrap();
+        assert_eq!(a.seed(), b.seed());
+        assert_eq!(bincode::serialize(&a).unwrap(), bincode::serialize(&b).unwrap());
+
+        let ints: Vec<u64> = (0..1000).collect();
+        let xf = XorFilter::with_hasher(&ints, 8, 3, MultiplyShift).unwrap();
+        assert!(ints.iter().all(|k| xf.query(k)));
+        let encoded = bincode::serialize(&xf).unwrap();
+        assert!(bincode::deserialize::<XorFilter>(&encoded).is_err());
+
+        // Hashes 0 and 1 fall into the same three slots under every seed
+        let stuck = XorFilter::with_hasher(&[0_u64, 1], 8, 3, Identity);
+        assert_eq!(stuck.err(), Some(FilterError::BuildFailed));
+
+        let empty: Vec<u64> = vec![];
+        let xf = XorFilter::new(&empty).unwrap();
+        assert!(xf.is_empty());
+        assert_eq!(xf.size_of(), xf.space_bytes());
+    }
+}